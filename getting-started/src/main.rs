@@ -16,27 +16,11 @@ pub struct App {
 
 impl App {
     fn render(&mut self, args: &RenderArgs) {
-        use graphics::*;
-
-        const GREEN: [f32; 4] = [0.0, 1.0, 0.0, 1.0];
-        const RED: [f32; 4] = [1.0, 0.0, 0.0, 1.0];
-
-        let square = rectangle::square(0.0, 0.0, 50.0);
         let rotation = self.rotation;
-        let (x, y) = (args.window_size[0] / 2.0, args.window_size[1] / 2.0);
+        let window_size = args.window_size;
 
         self.gl.draw(args.viewport(), |c, gl| {
-            // Clear the screen.
-            clear(GREEN, gl);
-
-            let transform = c
-                .transform
-                .trans(x, y)
-                .rot_rad(rotation)
-                .trans(-25.0, -25.0);
-
-            // Draw a box rotating around the middle of the screen.
-            rectangle(RED, square, transform, gl);
+            draw(rotation, window_size, &c, gl);
         });
     }
 
@@ -46,6 +30,34 @@ impl App {
     }
 }
 
+/// Draws the square turned by `rotation`, with any graphics backend.
+fn draw<G: graphics::Graphics>(
+    rotation: f64,
+    window_size: [f64; 2],
+    c: &graphics::Context,
+    gl: &mut G,
+) {
+    use graphics::*;
+
+    const GREEN: [f32; 4] = [0.0, 1.0, 0.0, 1.0];
+    const RED: [f32; 4] = [1.0, 0.0, 0.0, 1.0];
+
+    let square = rectangle::square(0.0, 0.0, 50.0);
+    let (x, y) = (window_size[0] / 2.0, window_size[1] / 2.0);
+
+    // Clear the screen.
+    clear(GREEN, gl);
+
+    let transform = c
+        .transform
+        .trans(x, y)
+        .rot_rad(rotation)
+        .trans(-25.0, -25.0);
+
+    // Draw a box rotating around the middle of the screen.
+    rectangle(RED, square, transform, gl);
+}
+
 fn main() {
     // Change this to OpenGL::V2_1 if not working.
     let opengl = OpenGL::V3_2;
@@ -74,3 +86,6 @@ fn main() {
         }
     }
 }
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,28 @@
+extern crate test_util;
+
+use self::test_util::{Draw, RecordingGraphics};
+use super::draw;
+use std::f64::consts::{FRAC_PI_4, SQRT_2};
+
+const GREEN: [f32; 4] = [0.0, 1.0, 0.0, 1.0];
+const RED: [f32; 4] = [1.0, 0.0, 0.0, 1.0];
+
+#[test]
+fn draws_square_in_the_middle() {
+    let mut g = RecordingGraphics::new(200.0, 200.0);
+    let c = g.context();
+    draw(0.0, [200.0, 200.0], &c, &mut g);
+    assert_eq!(g.draws[0], Draw::Clear(GREEN));
+    assert_eq!(g.fills([75.0, 75.0, 50.0, 50.0]), [RED]);
+}
+
+#[test]
+fn turns_square_around_its_middle() {
+    let mut g = RecordingGraphics::new(200.0, 200.0);
+    let c = g.context();
+    draw(FRAC_PI_4, [200.0, 200.0], &c, &mut g);
+    // Turned by 45 degrees, the corners reach out to half the diagonal.
+    let half = 25.0 * SQRT_2;
+    let rect = [100.0 - half, 100.0 - half, 2.0 * half, 2.0 * half];
+    assert_eq!(g.fills(rect), [RED]);
+}
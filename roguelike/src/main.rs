@@ -10,16 +10,19 @@ extern crate opengl_graphics;
 use glutin_window::GlutinWindow;
 use piston::WindowSettings;
 
+#[allow(unused_imports)]
 use piston::event_loop::{EventLoop, EventSettings, Events};
 use piston::input::{Button, ButtonState, Key};
 use piston::{ButtonEvent, RenderEvent};
 
 use graphics::character::CharacterCache;
+use graphics::{Context, Graphics};
 use opengl_graphics::{Filter, GlGraphics, GlyphCache, OpenGL, TextureSettings};
 
 type Colour = [f32; 4];
 
 const RED: Colour = [1.0, 0.0, 0.0, 1.0];
+#[allow(dead_code)]
 const GREEN: Colour = [0.0, 1.0, 0.0, 1.0];
 const BLUE: Colour = [0.0, 0.0, 1.0, 1.0];
 const WHITE: Colour = [1.0; 4];
@@ -61,6 +64,21 @@ impl Object {
             colour,
         }
     }
+
+    /// Moves one tile with the arrow keys.
+    pub fn event<E: ButtonEvent>(&mut self, e: &E) {
+        if let Some(k) = e.button_args() {
+            if k.state == ButtonState::Press {
+                match k.button {
+                    Button::Keyboard(Key::Up) => self.y -= PIXEL_SIZE as i32,
+                    Button::Keyboard(Key::Down) => self.y += PIXEL_SIZE as i32,
+                    Button::Keyboard(Key::Left) => self.x -= PIXEL_SIZE as i32,
+                    Button::Keyboard(Key::Right) => self.x += PIXEL_SIZE as i32,
+                    _ => (),
+                }
+            }
+        }
+    }
 }
 
 type Map = Vec<Vec<Tile>>;
@@ -71,13 +89,48 @@ fn make_map() -> Map {
     map
 }
 
+/// Draws the map with the player on top.
+fn render<G, C>(map: &Map, player: &Object, glyphs: &mut C, c: &Context, g: &mut G)
+where
+    G: Graphics,
+    C: CharacterCache<Texture = G::Texture>,
+    C::Error: std::fmt::Debug,
+{
+    graphics::clear(BLUE, g);
+
+    for i in 0..WORLD_SIZE {
+        for j in 0..WORLD_SIZE {
+            let pos: [f64; 4] = [
+                PIXEL_SIZE * i as f64,
+                PIXEL_SIZE * j as f64,
+                PIXEL_SIZE * (i + 1) as f64,
+                PIXEL_SIZE * (j + 1) as f64,
+            ];
+            graphics::Rectangle::new(map[i as usize][j as usize].colour).draw(
+                pos,
+                &c.draw_state,
+                c.transform,
+                g,
+            );
+        }
+    }
+    use graphics::Transformed;
+    let character = glyphs.character(32, player.character).unwrap();
+    graphics::Image::new_color(player.colour).draw(
+        character.texture,
+        &c.draw_state,
+        c.transform.trans(player.x as f64, player.y as f64),
+        g,
+    );
+}
+
 fn main() {
     let opengl = OpenGL::V3_2;
     let settings = WindowSettings::new("Roguelike", [512; 2]).exit_on_esc(true);
     let mut window: GlutinWindow = settings.build().expect("Could not create window");
     let mut gl = GlGraphics::new(opengl);
     let texture_settings = TextureSettings::new().filter(Filter::Nearest);
-    let ref mut glyphs = GlyphCache::new("assets/FiraSans-Regular.ttf", (), texture_settings)
+    let mut glyphs = GlyphCache::new("assets/FiraSans-Regular.ttf", (), texture_settings)
         .expect("Could not load font");
 
     let map = make_map();
@@ -87,44 +140,49 @@ fn main() {
     while let Some(e) = events.next(&mut window) {
         if let Some(r) = e.render_args() {
             gl.draw(r.viewport(), |c, g| {
-                graphics::clear(BLUE, g);
-
-                for i in 0..WORLD_SIZE {
-                    for j in 0..WORLD_SIZE {
-                        let pos: [f64; 4] = [
-                            PIXEL_SIZE * i as f64,
-                            PIXEL_SIZE * j as f64,
-                            PIXEL_SIZE * (i + 1) as f64,
-                            PIXEL_SIZE * (j + 1) as f64,
-                        ];
-                        graphics::Rectangle::new(map[i as usize][j as usize].colour).draw(
-                            pos,
-                            &c.draw_state,
-                            c.transform,
-                            g,
-                        );
-                    }
-                }
-                use graphics::Transformed;
-                    let character = glyphs.character(32, player.character).unwrap();
-                    graphics::Image::new_color(player.colour).draw(
-                        character.texture,
-                        &c.draw_state,
-                        c.transform.trans(player.x as f64, player.y as f64),
-                        g,
-                    );
+                render(&map, &player, &mut glyphs, &c, g);
             });
         }
-        if let Some(k) = e.button_args() {
-            if k.state == ButtonState::Press {
-                match k.button {
-                    Button::Keyboard(Key::Up) => player.y -= PIXEL_SIZE as i32,
-                    Button::Keyboard(Key::Down) => player.y += PIXEL_SIZE as i32,
-                    Button::Keyboard(Key::Left) => player.x -= PIXEL_SIZE as i32,
-                    Button::Keyboard(Key::Right) => player.x += PIXEL_SIZE as i32,
-                    _ => (),
-                }
+        player.event(&e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_util::{Draw, InputScript, RecordingGlyphs, RecordingGraphics};
+
+    #[test]
+    fn draws_map_and_player() {
+        let mut g = RecordingGraphics::new(512.0, 512.0);
+        let c = g.context();
+        let mut glyphs = RecordingGlyphs::new();
+        let player = Object::new(64, 32, '@', RED);
+        render(&make_map(), &player, &mut glyphs, &c, &mut g);
+        assert_eq!(g.draws[0], Draw::Clear(BLUE));
+        // Tiles are given their far corner as their size, as in the
+        // chapters, with later tiles covering the overflow.
+        assert_eq!(g.fills([0.0, 0.0, 32.0, 32.0]), [WHITE]);
+        assert_eq!(g.fills([256.0, 256.0, 288.0, 288.0]), [BLACK]);
+        match g.draws.last() {
+            Some(&Draw::Glyph {
+                ch, rect, color, ..
+            }) => {
+                assert_eq!(ch, '@');
+                assert_eq!(color, RED);
+                assert_eq!([rect[0], rect[1]], [64.0, 32.0]);
             }
+            draw => panic!("expected the player, found {:?}", draw),
         }
     }
+
+    #[test]
+    fn moves_player_with_arrows() {
+        let script: InputScript = "key Right; key Right; key Down; key Left; key Up"
+            .parse()
+            .unwrap();
+        let mut player = Object::new(0, 0, '@', RED);
+        script.play(|e| player.event(e));
+        assert_eq!((player.x, player.y), (32, 0));
+    }
 }
@@ -0,0 +1,558 @@
+//! Commands working on puzzle files without opening a window.
+//!
+//! Every command prints plain text, or JSON with `--json`, and exits with
+//! `SUCCESS`, with `FAILURE` when a puzzle does not pass, or with `ERROR`
+//! when the arguments or files cannot be used.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::formats;
+use crate::gameboard::{value_char, Gameboard, Geometry};
+use crate::generator::{self, Difficulty};
+use crate::grader::{self, cell_name};
+use crate::rules::Rules;
+use crate::solver::{self, Uniqueness};
+use crate::stats::quote;
+
+/// Names of the commands.
+pub const COMMANDS: [&str; 5] =
+    ["solve", "check", "grade", "convert", "generate"];
+
+/// Exit code when every puzzle passed.
+pub const SUCCESS: i32 = 0;
+/// Exit code when a puzzle has no solution or more than one, or no puzzle
+/// of the requested difficulty was generated.
+pub const FAILURE: i32 = 1;
+/// Exit code for bad arguments and unreadable or unwritable files.
+pub const ERROR: i32 = 2;
+
+/// What a command printed and how it ended.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Output {
+    /// Results, for standard output.
+    pub stdout: String,
+    /// Problems with single puzzles, for standard error.
+    pub stderr: String,
+    /// Exit code.
+    pub code: i32,
+}
+
+impl Output {
+    fn fail(&mut self, message: String) {
+        self.stderr.push_str(&message);
+        self.stderr.push('\n');
+        self.code = FAILURE;
+    }
+}
+
+/// Arguments following the command name.
+struct Options {
+    files: Vec<String>,
+    rules: Option<String>,
+    geometry: Geometry,
+    seed: Option<u64>,
+    json: bool,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Options, String> {
+        let mut options = Options {
+            files: vec![],
+            rules: None,
+            geometry: Geometry::CLASSIC,
+            seed: None,
+            json: false,
+        };
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| format!("`{}` needs a value", arg))
+            };
+            match arg.as_str() {
+                "--json" => options.json = true,
+                "--rules" => options.rules = Some(value()?.clone()),
+                "--size" => options.geometry = value()?.parse()?,
+                "--seed" => {
+                    let seed = value()?;
+                    options.seed = Some(
+                        seed.parse()
+                            .map_err(|_| format!("invalid seed `{}`", seed))?,
+                    );
+                }
+                _ if arg.starts_with("--") => {
+                    return Err(format!("unknown option `{}`", arg))
+                }
+                _ => options.files.push(arg.clone()),
+            }
+        }
+        Ok(options)
+    }
+
+    /// The positional arguments, which must be `names`.
+    fn files(&self, names: &[&str]) -> Result<&[String], String> {
+        if self.files.len() != names.len() {
+            return Err(format!("expected {}", names.join(" ")));
+        }
+        Ok(&self.files)
+    }
+
+    /// Reads the puzzles in the one file given, adding the variant rules.
+    fn puzzles(&self) -> Result<Vec<Gameboard>, String> {
+        let file = &self.files(&["FILE"])?[0];
+        let mut puzzles = formats::load(file).map_err(|e| e.to_string())?;
+        if let Some(ref rules_file) = self.rules {
+            for puzzle in puzzles.iter_mut() {
+                puzzle.rules = Rules::load(rules_file, puzzle.geometry)?;
+            }
+        }
+        Ok(puzzles)
+    }
+}
+
+/// Runs the command named by the first of `args`.
+pub fn run(args: &[String]) -> Result<Output, String> {
+    let (command, args) = args.split_first().ok_or("missing command")?;
+    let options = Options::parse(args)?;
+    match command.as_str() {
+        "solve" => solve(&options),
+        "check" => check(&options),
+        "grade" => grade(&options),
+        "convert" => convert(&options),
+        "generate" => generate(&options),
+        _ => Err(format!("unknown command `{}`", command)),
+    }
+}
+
+/// Prints the solution of every puzzle, one line of values each.
+fn solve(options: &Options) -> Result<Output, String> {
+    let mut output = Output::default();
+    let mut results = vec![];
+    let puzzles = options.puzzles()?;
+    for (k, puzzle) in puzzles.iter().enumerate() {
+        let label = label(k, puzzles.len());
+        let uniqueness = solver::uniqueness(puzzle);
+        let solution = solver::solve(puzzle).map(|solved| values(&solved));
+        if uniqueness != Uniqueness::Unique {
+            output.fail(format!("{}: {}", label, describe(uniqueness)));
+        }
+        if options.json {
+            results.push(format!(
+                "{{\"puzzle\": {}, \"uniqueness\": {}, \"solution\": {}}}",
+                quote(&puzzle.to_sdm()),
+                quote(uniqueness_name(uniqueness)),
+                solution.as_deref().map_or("null".into(), quote),
+            ));
+        } else if let Some(solution) = solution {
+            results.push(solution);
+        }
+    }
+    output.stdout = print(options.json, &results);
+    Ok(output)
+}
+
+/// Reports whether every puzzle has exactly one solution, naming any
+/// givens that clash.
+fn check(options: &Options) -> Result<Output, String> {
+    let mut output = Output::default();
+    let mut results = vec![];
+    let puzzles = options.puzzles()?;
+    for (k, puzzle) in puzzles.iter().enumerate() {
+        let label = label(k, puzzles.len());
+        let uniqueness = solver::uniqueness(puzzle);
+        let conflicts = conflicts(puzzle);
+        if uniqueness != Uniqueness::Unique {
+            output.code = FAILURE;
+        }
+        if options.json {
+            let conflicts: Vec<String> =
+                conflicts.iter().map(|cell| quote(cell)).collect();
+            results.push(format!(
+                "{{\"puzzle\": {}, \"uniqueness\": {}, \"conflicts\": [{}]}}",
+                quote(&puzzle.to_sdm()),
+                quote(uniqueness_name(uniqueness)),
+                conflicts.join(", "),
+            ));
+        } else if conflicts.is_empty() {
+            results.push(format!("{}: {}", label, describe(uniqueness)));
+        } else {
+            results.push(format!(
+                "{}: {}, givens clash at {}",
+                label,
+                describe(uniqueness),
+                conflicts.join(" "),
+            ));
+        }
+    }
+    output.stdout = print(options.json, &results);
+    Ok(output)
+}
+
+/// Grades every puzzle by the techniques needed to solve it.
+fn grade(options: &Options) -> Result<Output, String> {
+    let mut output = Output::default();
+    let mut results = vec![];
+    let puzzles = options.puzzles()?;
+    for (k, puzzle) in puzzles.iter().enumerate() {
+        let label = label(k, puzzles.len());
+        let uniqueness = solver::uniqueness(puzzle);
+        if uniqueness != Uniqueness::Unique {
+            output.fail(format!("{}: {}", label, describe(uniqueness)));
+            continue;
+        }
+        let grade = grader::grade(puzzle);
+        let hardest = grade.hardest().map(|technique| technique.name());
+        if options.json {
+            results.push(format!(
+                "{{\"puzzle\": {}, \"difficulty\": {}, \"score\": {}, \
+                 \"solved\": {}, \"hardest\": {}, \"steps\": {}}}",
+                quote(&puzzle.to_sdm()),
+                quote(&grade.difficulty().to_string()),
+                grade.score,
+                grade.solved,
+                hardest.map_or("null".into(), quote),
+                grade.steps.len(),
+            ));
+        } else {
+            let mut line = format!(
+                "{}: {}, score {}",
+                label,
+                grade.difficulty(),
+                grade.score
+            );
+            if let Some(hardest) = hardest {
+                line.push_str(&format!(", hardest {}", hardest));
+            }
+            if !grade.solved {
+                line.push_str(", needs guessing");
+            }
+            results.push(line);
+        }
+    }
+    output.stdout = print(options.json, &results);
+    Ok(output)
+}
+
+/// Writes the puzzles of one file to another, in the format its extension
+/// names.
+fn convert(options: &Options) -> Result<Output, String> {
+    let files = options.files(&["IN", "OUT"])?;
+    let (infile, outfile) = (&files[0], &files[1]);
+    let puzzles = formats::load(infile).map_err(|e| e.to_string())?;
+    formats::save(outfile, &puzzles)
+        .map_err(|e| format!("failed to write `{}`: {}", outfile, e))?;
+    let format = formats::by_extension(outfile).unwrap_or(&formats::Sdm);
+    let stdout = if options.json {
+        format!(
+            "{{\"puzzles\": {}, \"format\": {}}}\n",
+            puzzles.len(),
+            quote(format.name())
+        )
+    } else {
+        format!(
+            "wrote {} to `{}` as {}\n",
+            count(puzzles.len(), "puzzle"),
+            outfile,
+            format.name()
+        )
+    };
+    Ok(Output {
+        stdout,
+        ..Output::default()
+    })
+}
+
+/// Generates a puzzle of the difficulty given, writing it to the file
+/// given or else printing it as SDM.
+///
+/// When the generator settles for another difficulty the puzzle is still
+/// written, but the difficulty reached is reported and the command fails.
+///
+/// Without a seed one is picked from the clock and reported, so the puzzle
+/// can be generated again.
+fn generate(options: &Options) -> Result<Output, String> {
+    let (difficulty, outfile) = match &options.files[..] {
+        [difficulty] => (difficulty, None),
+        [difficulty, outfile] => (difficulty, Some(outfile)),
+        _ => return Err("expected DIFFICULTY [OUT]".into()),
+    };
+    let difficulty: Difficulty = difficulty.parse()?;
+    let mut output = Output::default();
+    let seed = options.seed.unwrap_or_else(|| {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH);
+        let seed = now.map(|d| d.as_nanos() as u64).unwrap_or_default();
+        if !options.json {
+            output.stderr = format!("seed: {}\n", seed);
+        }
+        seed
+    });
+    let (puzzle, graded) =
+        generator::generate(options.geometry, difficulty, seed);
+    if let Some(outfile) = outfile {
+        formats::save(outfile, std::slice::from_ref(&puzzle))
+            .map_err(|e| format!("failed to write `{}`: {}", outfile, e))?;
+    }
+    if options.json {
+        output.stdout = format!(
+            "{{\"puzzle\": {}, \"difficulty\": {}, \"seed\": {}}}\n",
+            quote(&puzzle.to_sdm()),
+            quote(&graded.to_string()),
+            seed
+        );
+    } else if outfile.is_none() {
+        output.stdout = format!("{}\n", puzzle.to_sdm());
+    }
+    if graded != difficulty {
+        output.fail(format!(
+            "no {} puzzle found, generated a {} one instead",
+            difficulty, graded
+        ));
+    }
+    Ok(output)
+}
+
+/// Lines of text, or a JSON object listing the puzzles.
+fn print(json: bool, results: &[String]) -> String {
+    if !json {
+        return results.iter().map(|line| format!("{}\n", line)).collect();
+    }
+    let mut s = String::from("{\n  \"puzzles\": [");
+    for (k, result) in results.iter().enumerate() {
+        s.push_str(if k == 0 { "\n    " } else { ",\n    " });
+        s.push_str(result);
+    }
+    s.push_str(if results.is_empty() {
+        "]\n}\n"
+    } else {
+        "\n  ]\n}\n"
+    });
+    s
+}
+
+/// Names puzzle `k` of `count`, padding the number so that the labels of
+/// all puzzles line up.
+fn label(k: usize, count: usize) -> String {
+    let width = count.to_string().len();
+    format!("puzzle {:>width$}", k + 1, width = width)
+}
+
+/// `n` followed by `noun`, in the plural unless `n` is 1.
+fn count(n: usize, noun: &str) -> String {
+    format!("{} {}{}", n, noun, if n == 1 { "" } else { "s" })
+}
+
+/// All values of `gameboard` in reading order, as written by SDM.
+fn values(gameboard: &Gameboard) -> String {
+    gameboard
+        .values()
+        .iter()
+        .flatten()
+        .map(|&value| value_char(value).unwrap_or('0'))
+        .collect()
+}
+
+/// Givens clashing with another given.
+fn conflicts(puzzle: &Gameboard) -> Vec<String> {
+    let mut puzzle = puzzle.clone();
+    puzzle.update_conflicts();
+    let mut cells = vec![];
+    for (i, row) in puzzle.cells.iter().enumerate() {
+        for (j, cell) in row.iter().enumerate() {
+            if cell.loaded && cell.invalid {
+                cells.push(cell_name([j, i]));
+            }
+        }
+    }
+    cells
+}
+
+fn uniqueness_name(uniqueness: Uniqueness) -> &'static str {
+    match uniqueness {
+        Uniqueness::Unsolvable => "unsolvable",
+        Uniqueness::Unique => "unique",
+        Uniqueness::Ambiguous => "ambiguous",
+    }
+}
+
+fn describe(uniqueness: Uniqueness) -> &'static str {
+    match uniqueness {
+        Uniqueness::Unsolvable => "no solution",
+        Uniqueness::Unique => "one solution",
+        Uniqueness::Ambiguous => "more than one solution",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env::temp_dir;
+    use std::fs::{read_to_string, remove_file, write};
+    use std::process;
+
+    const PUZZLE: &str = "\
+        530070000600195000098000060800060003400803001\
+        700020006060000280000419005000080079";
+    const SOLUTION: &str = "\
+        534678912672195348198342567859761423426853791\
+        713924856961537284287419635345286179";
+
+    /// A path in the temporary directory for `name`, unique to this test
+    /// run so that concurrent runs do not share files.
+    fn temp_file(name: &str) -> String {
+        let name = format!("sudoku-{}-{}", process::id(), name);
+        temp_dir().join(name).to_str().unwrap().to_string()
+    }
+
+    /// Writes `puzzles` to a temporary file for `name`.
+    fn puzzle_file(name: &str, puzzles: &[&str]) -> String {
+        let file = temp_file(name);
+        write(&file, puzzles.join("\n")).unwrap();
+        file
+    }
+
+    fn run(args: &[&str]) -> Result<Output, String> {
+        let args: Vec<String> = args.iter().map(|&arg| arg.into()).collect();
+        super::run(&args)
+    }
+
+    #[test]
+    fn solves_and_checks() {
+        let ambiguous = format!("00{}", &PUZZLE[2..]);
+        let clash = format!("55{}", &PUZZLE[2..]);
+        let file = puzzle_file("cli-solve.sdm", &[PUZZLE, &ambiguous, &clash]);
+
+        let output = run(&["solve", &file]).unwrap();
+        assert_eq!(output.code, FAILURE);
+        let lines: Vec<&str> = output.stdout.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0], SOLUTION);
+        assert_eq!(
+            output.stderr,
+            "puzzle 2: more than one solution\npuzzle 3: no solution\n"
+        );
+
+        let output = run(&["check", &file]).unwrap();
+        assert_eq!(output.code, FAILURE);
+        assert_eq!(
+            output.stdout,
+            "puzzle 1: one solution\npuzzle 2: more than one solution\n\
+             puzzle 3: no solution, givens clash at r1c1 r1c2\n"
+        );
+
+        let output = run(&["check", &file, "--json"]).unwrap();
+        let conflicts = "\"conflicts\": [\"r1c1\", \"r1c2\"]";
+        assert!(output.stdout.contains(conflicts));
+        let one = puzzle_file("cli-one.sdm", &[PUZZLE]);
+        assert_eq!(run(&["check", &one]).unwrap().code, SUCCESS);
+        remove_file(file).unwrap();
+        remove_file(one).unwrap();
+    }
+
+    #[test]
+    fn grades_and_converts() {
+        let file = puzzle_file("cli-grade.sdm", &[PUZZLE]);
+        let output = run(&["grade", &file]).unwrap();
+        assert_eq!(output.code, SUCCESS);
+        assert!(output.stdout.starts_with("puzzle 1: easy, score "));
+
+        let output = run(&["grade", "--json", &file]).unwrap();
+        assert!(output.stdout.starts_with("{\n  \"puzzles\": [\n    {"));
+        assert!(output.stdout.contains("\"difficulty\": \"easy\""));
+        assert!(output.stdout.contains("\"solved\": true"));
+
+        let outfile = temp_file("cli-grade.sdk");
+        let output = run(&["convert", &file, &outfile]).unwrap();
+        assert_eq!(
+            output.stdout,
+            format!("wrote 1 puzzle to `{}` as sdk\n", outfile)
+        );
+        assert_eq!(formats::load(&outfile).unwrap()[0].to_sdm(), PUZZLE);
+        assert!(read_to_string(&outfile).unwrap().starts_with("53.."));
+        remove_file(file).unwrap();
+        remove_file(outfile).unwrap();
+    }
+
+    #[test]
+    fn aligns_puzzle_numbers() {
+        let clash = format!("55{}", &PUZZLE[2..]);
+        let mut puzzles = vec![PUZZLE; 10];
+        puzzles[8] = &clash;
+        let file = puzzle_file("cli-align.sdm", &puzzles);
+
+        let output = run(&["check", &file]).unwrap();
+        let lines: Vec<&str> = output.stdout.lines().collect();
+        assert_eq!(lines[0], "puzzle  1: one solution");
+        assert_eq!(
+            lines[8],
+            "puzzle  9: no solution, givens clash at r1c1 r1c2"
+        );
+        assert_eq!(lines[9], "puzzle 10: one solution");
+
+        let output = run(&["grade", &file]).unwrap();
+        assert_eq!(output.stderr, "puzzle  9: no solution\n");
+        assert!(output.stdout.starts_with("puzzle  1: easy, score "));
+
+        let outfile = temp_file("cli-align.txt");
+        let output = run(&["convert", &file, &outfile]).unwrap();
+        assert!(output.stdout.starts_with("wrote 10 puzzles to "));
+        remove_file(file).unwrap();
+        remove_file(outfile).unwrap();
+    }
+
+    #[test]
+    fn refuses_formats_that_cannot_hold_the_board() {
+        // One row per line states the size of the board.
+        let rows = ["1234", "3412", "2143", "4321"];
+        let file = puzzle_file("cli-small.sdm", &rows);
+        let outfile = temp_file("cli-small.opensudoku");
+        let error = run(&["convert", &file, &outfile]).unwrap_err();
+        assert_eq!(
+            error,
+            format!(
+                "failed to write `{}`: opensudoku cannot hold 4x4 boards",
+                outfile
+            )
+        );
+        assert!(formats::load(&outfile).is_err());
+        remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn generates_puzzles() {
+        let output =
+            run(&["generate", "easy", "--size", "6", "--seed", "7"]).unwrap();
+        assert_eq!(output.code, SUCCESS);
+        assert_eq!(output.stderr, "");
+        let puzzle = output.stdout.trim();
+        assert_eq!(puzzle.len(), 36);
+        let again = run(&["generate", "easy", "--seed", "7", "--size", "6"]);
+        assert_eq!(again.unwrap().stdout.trim(), puzzle);
+
+        let output =
+            run(&["generate", "easy", "--size", "6", "--json"]).unwrap();
+        assert!(output.stdout.contains("\"seed\": "));
+        assert_eq!(output.stderr, "");
+
+        let output =
+            run(&["generate", "expert", "--size", "4", "--seed", "7"]).unwrap();
+        assert_eq!(output.code, FAILURE);
+        assert_eq!(output.stdout.trim().len(), 16);
+        assert!(output.stderr.starts_with("no expert puzzle found"));
+    }
+
+    #[test]
+    fn rejects_bad_arguments() {
+        assert_eq!(run(&["play"]), Err("unknown command `play`".into()));
+        assert_eq!(run(&["solve"]), Err("expected FILE".into()));
+        assert_eq!(
+            run(&["solve", "a", "--colour"]),
+            Err("unknown option `--colour`".into())
+        );
+        assert_eq!(
+            run(&["generate", "easy", "--seed"]),
+            Err("`--seed` needs a value".into())
+        );
+        assert_eq!(run(&["convert", "a.sdm"]), Err("expected IN OUT".into()));
+        assert!(run(&["check", "missing.sdm"]).is_err());
+    }
+}
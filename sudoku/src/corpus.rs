@@ -0,0 +1,63 @@
+//! Puzzles shared by the solver and grader tests.
+
+use crate::gameboard::SIZE;
+use crate::{Difficulty, Gameboard};
+
+/// Puzzles with the difficulty the grader gives them, from easiest to
+/// hardest, `.` marks a blank.
+pub(crate) const CORPUS: &[(&str, Difficulty)] = &[
+    (
+        "..3.2.6..9..3.5..1..18.64....81.29..7.......8..67.82....26.95..8..2.3..9..5.1.3..",
+        Difficulty::Easy,
+    ),
+    (
+        "..............3.85..1.2.......5.7.....4...1...9.......5......73..2.1........4...9",
+        Difficulty::Easy,
+    ),
+    (
+        "4.....8.5.3..........7......2.....6.....8.4......1.......6.3.7.5..2.....1.4......",
+        Difficulty::Medium,
+    ),
+    (
+        "52...6.........7.13...........4..8..6......5...........418.........3..2...87.....",
+        Difficulty::Medium,
+    ),
+    (
+        ".164.....2....9...4......62.7.23.1..1.......3..3.87.4.96......5...8....7.....682.",
+        Difficulty::Hard,
+    ),
+    (
+        "8..........36......7..9.2...5...7.......457.....1...3...1....68..85...1..9....4..",
+        Difficulty::Expert,
+    ),
+    (
+        "1....7.9..3..2...8..96..5....53..9...1..8...26....4...3......1..4......7..7...3..",
+        Difficulty::Expert,
+    ),
+    (
+        "12.3....435....1....4........54..2..6...7.........8.9...31..5.......9.7.....6...8",
+        Difficulty::Expert,
+    ),
+    (
+        "1.......2.9.4...5...6...7...5.9.3.......7.......85..4.7.....6...3...9.8...2.....1",
+        Difficulty::Expert,
+    ),
+];
+
+/// The first puzzle of the corpus with `difficulty`.
+pub(crate) fn puzzle(difficulty: Difficulty) -> Gameboard {
+    let &(puzzle, _) = CORPUS
+        .iter()
+        .find(|&&(_, d)| d == difficulty)
+        .expect("corpus covers every difficulty");
+    board(puzzle)
+}
+
+/// A 9x9 board from 81 characters, anything but a digit being a blank.
+pub(crate) fn board(puzzle: &str) -> Gameboard {
+    let mut cells = [[0; SIZE]; SIZE];
+    for (k, c) in puzzle.chars().enumerate() {
+        cells[k / SIZE][k % SIZE] = c.to_digit(10).unwrap_or(0) as u8;
+    }
+    Gameboard::from_cells(cells)
+}
@@ -0,0 +1,533 @@
+//! Readers and writers for puzzle interchange formats.
+//!
+//! Every format is a unit struct implementing `Format`. `load` picks the
+//! format of a file from its extension, falling back to looking at the
+//! content when the extension is unknown.
+
+use std::error::Error;
+use std::fmt;
+use std::fs::{read_to_string, write};
+use std::io;
+use std::path::Path;
+
+use crate::gameboard::{Gameboard, Geometry, SIZE};
+use crate::sdm::{self, Location, SdmError};
+
+/// A file format holding one or more puzzles.
+///
+/// Only the givens of a puzzle are written; player entries and pencil marks
+/// are dropped.
+pub trait Format {
+    /// Short name of the format.
+    fn name(&self) -> &'static str;
+
+    /// File extensions of the format, without the leading dot.
+    fn extensions(&self) -> &'static [&'static str];
+
+    /// Whether `data` looks like it is in this format.
+    fn sniff(&self, data: &str) -> bool;
+
+    /// Reads the puzzles in `data`, naming `file` in errors.
+    fn read(
+        &self,
+        data: &str,
+        file: &str,
+    ) -> Result<Vec<Gameboard>, FormatError>;
+
+    /// Writes the givens of `puzzles`.
+    fn write(&self, puzzles: &[Gameboard]) -> String;
+
+    /// Whether the format can hold boards of `geometry`.
+    fn holds(&self, _geometry: Geometry) -> bool {
+        true
+    }
+}
+
+/// All supported formats, in the order they are tried when sniffing.
+pub const FORMATS: &[&dyn Format] =
+    &[&OpenSudoku, &SimpleSudoku, &Sdm, &OneLine, &Sdk];
+
+/// Error from loading a puzzle file.
+#[derive(Debug)]
+pub enum FormatError {
+    /// The file could not be read.
+    Io {
+        /// Name of the file.
+        file: String,
+        /// Underlying error.
+        error: io::Error,
+    },
+    /// Neither the extension nor the content matched a known format.
+    Unrecognized {
+        /// Name of the file.
+        file: String,
+    },
+    /// The cells of a puzzle could not be read.
+    Grid(SdmError),
+    /// Other malformed content.
+    Invalid {
+        /// Position of the problem.
+        at: Location,
+        /// Description of the problem.
+        message: String,
+    },
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FormatError::Io { file, error } => {
+                write!(f, "failed to read `{}`: {}", file, error)
+            }
+            FormatError::Unrecognized { file } => {
+                write!(f, "`{}` is not in a known puzzle format", file)
+            }
+            FormatError::Grid(error) => error.fmt(f),
+            FormatError::Invalid { at, message } => {
+                write!(f, "{}: {}", at, message)
+            }
+        }
+    }
+}
+
+impl Error for FormatError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            FormatError::Io { error, .. } => Some(error),
+            FormatError::Grid(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<SdmError> for FormatError {
+    fn from(error: SdmError) -> FormatError {
+        FormatError::Grid(error)
+    }
+}
+
+/// Finds a format by name, e.g. `sdk`.
+pub fn by_name(name: &str) -> Option<&'static dyn Format> {
+    FORMATS.iter().copied().find(|format| format.name() == name)
+}
+
+/// Finds the format used for files named like `filename`.
+pub fn by_extension(filename: &str) -> Option<&'static dyn Format> {
+    let ext = Path::new(filename).extension()?.to_str()?.to_lowercase();
+    FORMATS
+        .iter()
+        .copied()
+        .find(|format| format.extensions().contains(&ext.as_str()))
+}
+
+/// Picks the format of `data` read from `filename`.
+pub fn detect(filename: &str, data: &str) -> Option<&'static dyn Format> {
+    by_extension(filename)
+        .or_else(|| FORMATS.iter().copied().find(|f| f.sniff(data)))
+}
+
+/// Reads all puzzles in the file `filename`, in whatever format it is.
+pub fn load(filename: &str) -> Result<Vec<Gameboard>, FormatError> {
+    let data = read_to_string(filename).map_err(|error| FormatError::Io {
+        file: filename.to_string(),
+        error,
+    })?;
+    let format =
+        detect(filename, &data).ok_or_else(|| FormatError::Unrecognized {
+            file: filename.to_string(),
+        })?;
+    format.read(&data, filename)
+}
+
+/// Writes `puzzles` to `filename` in the format its extension names, or as
+/// SDM if the extension is unknown.
+///
+/// Fails with `InvalidInput` if the format cannot hold one of the boards.
+pub fn save(filename: &str, puzzles: &[Gameboard]) -> io::Result<()> {
+    let format = by_extension(filename).unwrap_or(&Sdm);
+    if let Some(puzzle) = puzzles.iter().find(|p| !format.holds(p.geometry)) {
+        let size = puzzle.size();
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} cannot hold {}x{} boards", format.name(), size, size),
+        ));
+    }
+    write(filename, format.write(puzzles))
+}
+
+/// Lines of `data` that are not blank or `#` comments.
+fn content_lines(data: &str) -> impl Iterator<Item = &str> {
+    data.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+}
+
+/// Characters of cells, as written by `Gameboard::to_sdm`.
+const CELL_CHARS: &str = "0123456789ABCDEFGHIJKLMNOP";
+
+/// Whether the content lines of `data` all have the same length, for which
+/// `len_ok` holds, and consist of `CELL_CHARS` and `extra`.
+fn lines_of(data: &str, extra: &str, len_ok: impl Fn(usize) -> bool) -> bool {
+    let mut lines = content_lines(data).peekable();
+    let len = match lines.peek() {
+        Some(line) => line.chars().count(),
+        None => return false,
+    };
+    len_ok(len)
+        && lines.all(|line| {
+            line.chars().count() == len
+                && line
+                    .chars()
+                    .all(|c| CELL_CHARS.contains(c) || extra.contains(c))
+        })
+}
+
+/// Whether `len` cells make up a whole board.
+fn is_board(len: usize) -> bool {
+    (2..=len)
+        .take_while(|k| k * k <= len)
+        .any(|k| k * k == len && Geometry::for_size(k).is_some())
+}
+
+/// Whether `len` cells make up a row of a board.
+fn is_row(len: usize) -> bool {
+    Geometry::for_size(len).is_some()
+}
+
+/// The givens of `puzzle` in reading order, with `blank` for empty cells.
+fn givens(puzzle: &Gameboard, blank: char) -> Vec<char> {
+    puzzle
+        .to_sdm()
+        .chars()
+        .map(|c| if c == '0' { blank } else { c })
+        .collect()
+}
+
+/// The multi-puzzle SDM format, one puzzle of 81 digits per line with `0`
+/// for blanks.
+pub struct Sdm;
+
+impl Format for Sdm {
+    fn name(&self) -> &'static str {
+        "sdm"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["sdm"]
+    }
+
+    fn sniff(&self, data: &str) -> bool {
+        lines_of(data, "", is_board)
+    }
+
+    fn read(
+        &self,
+        data: &str,
+        file: &str,
+    ) -> Result<Vec<Gameboard>, FormatError> {
+        Ok(sdm::parse_all(data, file)?)
+    }
+
+    fn write(&self, puzzles: &[Gameboard]) -> String {
+        let lines: Vec<String> = puzzles.iter().map(|p| p.to_sdm()).collect();
+        lines.join("\n")
+    }
+}
+
+/// One puzzle of 81 characters per line, with `.` for blanks.
+pub struct OneLine;
+
+impl Format for OneLine {
+    fn name(&self) -> &'static str {
+        "line"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["txt"]
+    }
+
+    fn sniff(&self, data: &str) -> bool {
+        lines_of(data, ".", is_board)
+    }
+
+    fn read(
+        &self,
+        data: &str,
+        file: &str,
+    ) -> Result<Vec<Gameboard>, FormatError> {
+        Ok(sdm::parse_all(data, file)?)
+    }
+
+    fn write(&self, puzzles: &[Gameboard]) -> String {
+        let mut out = String::new();
+        for puzzle in puzzles {
+            out.extend(givens(puzzle, '.'));
+            out.push('\n');
+        }
+        out
+    }
+}
+
+/// SadMan Sudoku files: nine rows of nine cells with `.` for blanks, after
+/// optional `#` metadata lines.
+pub struct Sdk;
+
+impl Format for Sdk {
+    fn name(&self) -> &'static str {
+        "sdk"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["sdk"]
+    }
+
+    fn sniff(&self, data: &str) -> bool {
+        lines_of(data, ".", is_row)
+    }
+
+    fn read(
+        &self,
+        data: &str,
+        file: &str,
+    ) -> Result<Vec<Gameboard>, FormatError> {
+        Ok(sdm::parse_grids(data, file, "", true, usize::MAX)?)
+    }
+
+    fn write(&self, puzzles: &[Gameboard]) -> String {
+        let grids: Vec<String> = puzzles
+            .iter()
+            .map(|puzzle| {
+                let mut out = String::new();
+                for row in givens(puzzle, '.').chunks(puzzle.size()) {
+                    out.extend(row);
+                    out.push('\n');
+                }
+                out
+            })
+            .collect();
+        grids.join("\n")
+    }
+}
+
+/// Simple Sudoku files, with `|` between boxes and dashed lines between
+/// bands of boxes.
+pub struct SimpleSudoku;
+
+impl Format for SimpleSudoku {
+    fn name(&self) -> &'static str {
+        "ss"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["ss"]
+    }
+
+    fn sniff(&self, data: &str) -> bool {
+        content_lines(data).any(|line| line.contains('|'))
+    }
+
+    fn read(
+        &self,
+        data: &str,
+        file: &str,
+    ) -> Result<Vec<Gameboard>, FormatError> {
+        Ok(sdm::parse_grids(data, file, "|-+*", true, usize::MAX)?)
+    }
+
+    fn write(&self, puzzles: &[Gameboard]) -> String {
+        let grids: Vec<String> = puzzles
+            .iter()
+            .map(|puzzle| {
+                let Geometry {
+                    box_width,
+                    box_height,
+                } = puzzle.geometry;
+                let size = puzzle.size();
+                let dashes = "-".repeat(size + size / box_width - 1);
+                let mut out = String::new();
+                for (i, row) in givens(puzzle, '.').chunks(size).enumerate() {
+                    if i > 0 && i % box_height == 0 {
+                        out.push_str(&dashes);
+                        out.push('\n');
+                    }
+                    for (j, sec) in row.chunks(box_width).enumerate() {
+                        if j > 0 {
+                            out.push('|');
+                        }
+                        out.extend(sec);
+                    }
+                    out.push('\n');
+                }
+                out
+            })
+            .collect();
+        grids.join("\n")
+    }
+}
+
+/// OpenSudoku XML collections, each puzzle a `<game>` element whose `data`
+/// attribute holds 81 digits with `0` for blanks. The format only knows the
+/// classic board.
+pub struct OpenSudoku;
+
+impl Format for OpenSudoku {
+    fn name(&self) -> &'static str {
+        "opensudoku"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["opensudoku", "xml"]
+    }
+
+    fn sniff(&self, data: &str) -> bool {
+        data.trim_start().starts_with("<?xml") || data.contains("<opensudoku")
+    }
+
+    fn read(
+        &self,
+        data: &str,
+        file: &str,
+    ) -> Result<Vec<Gameboard>, FormatError> {
+        let invalid = |pos: usize, message: &str| {
+            let before = &data[..pos];
+            let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+            FormatError::Invalid {
+                at: Location {
+                    file: file.to_string(),
+                    line: before.matches('\n').count() + 1,
+                    column: before[line_start..].chars().count() + 1,
+                },
+                message: message.to_string(),
+            }
+        };
+        let mut puzzles = Vec::new();
+        for (pos, _) in data.match_indices("<game") {
+            let rest = &data[pos + "<game".len()..];
+            if !rest.starts_with(|c: char| c.is_whitespace() || c == '/') {
+                continue;
+            }
+            let tag = &rest[..rest.find('>').unwrap_or(rest.len())];
+            let cells = attribute(tag, "data")
+                .ok_or_else(|| invalid(pos, "`<game>` has no `data`"))?;
+            if cells.len() != SIZE * SIZE
+                || !cells.bytes().all(|b| b.is_ascii_digit())
+            {
+                return Err(invalid(
+                    pos,
+                    "`data` must hold 81 digits, with 0 for blanks",
+                ));
+            }
+            puzzles.push(sdm::parse(cells, file)?);
+        }
+        if puzzles.is_empty() {
+            return Err(invalid(0, "no `<game>` elements"));
+        }
+        Ok(puzzles)
+    }
+
+    fn write(&self, puzzles: &[Gameboard]) -> String {
+        let mut out = String::new();
+        out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        out.push_str("<opensudoku>\n");
+        for puzzle in puzzles {
+            out.push_str(&format!("  <game data=\"{}\"/>\n", puzzle.to_sdm()));
+        }
+        out.push_str("</opensudoku>\n");
+        out
+    }
+
+    fn holds(&self, geometry: Geometry) -> bool {
+        geometry == Geometry::CLASSIC
+    }
+}
+
+/// Value of the attribute `name` in the text of an XML start tag.
+fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let key = format!("{}=\"", name);
+    let (pos, _) = tag
+        .match_indices(&key)
+        .find(|&(pos, _)| tag[..pos].ends_with(char::is_whitespace))?;
+    tag[pos + key.len()..].split('"').next()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PUZZLE: &str = "016400000200009000400000062070230100100000003\
+                          003087040960000005000800007000006820";
+
+    fn puzzle() -> Gameboard {
+        sdm::parse(PUZZLE, "a.sdm").unwrap()
+    }
+
+    #[test]
+    fn round_trips_every_format() {
+        let puzzles = vec![puzzle(), Gameboard::from_cells([[0; SIZE]; SIZE])];
+        for format in FORMATS {
+            let data = format.write(&puzzles);
+            let read = format.read(&data, "out").unwrap();
+            assert_eq!(read, puzzles, "{}", format.name());
+            let single = format.write(&puzzles[..1]);
+            assert_eq!(detect("out", &single).unwrap().name(), format.name());
+        }
+    }
+
+    #[test]
+    fn reads_foreign_files() {
+        let ss = "\
+            .16|4..|...\n2..|..9|...\n4..|...|.62\n\
+            -----------\n\
+            .7.|23.|1..\n1..|...|..3\n..3|.87|.4.\n\
+            -----------\n\
+            96.|...|..5\n...|8..|..7\n...|..6|82.\n";
+        assert_eq!(SimpleSudoku.read(ss, "a.ss").unwrap(), vec![puzzle()]);
+
+        let xml = format!(
+            "<?xml version=\"1.0\"?>\n<opensudoku>\n  <name>Test</name>\n  \
+             <game created=\"2024-01-01\" data=\"{}\" note=\"\"/>\n\
+             </opensudoku>",
+            PUZZLE
+        );
+        assert_eq!(OpenSudoku.read(&xml, "a.xml").unwrap(), vec![puzzle()]);
+    }
+
+    #[test]
+    fn round_trips_other_sizes() {
+        let mut cells = vec![vec![0; 12]; 12];
+        cells[0][0] = 12;
+        cells[11][5] = 3;
+        let puzzles = vec![Gameboard::from_cells(&cells)];
+        for format in FORMATS.iter().filter(|f| f.name() != "opensudoku") {
+            let data = format.write(&puzzles);
+            assert_eq!(format.read(&data, "out").unwrap(), puzzles);
+            assert_eq!(detect("out", &data).unwrap().name(), format.name());
+        }
+        assert!(SimpleSudoku.write(&puzzles).starts_with("C...|....|....\n"));
+    }
+
+    #[test]
+    fn detects_formats() {
+        assert_eq!(by_extension("a.SDK").unwrap().name(), "sdk");
+        assert_eq!(by_extension("dir.ss/puzzle").map(|f| f.name()), None);
+        assert_eq!(detect("a.ss", PUZZLE).unwrap().name(), "ss");
+        assert_eq!(detect("a", PUZZLE).unwrap().name(), "sdm");
+        assert_eq!(
+            by_name("opensudoku").unwrap().extensions()[0],
+            "opensudoku"
+        );
+        assert!(detect("a", "hello").is_none());
+    }
+
+    #[test]
+    fn reports_bad_xml() {
+        let xml = "<opensudoku>\n  <game data=\"123\"/>\n</opensudoku>";
+        let error = OpenSudoku.read(xml, "a.xml").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "a.xml:2:3: `data` must hold 81 digits, with 0 for blanks"
+        );
+        let error = OpenSudoku.read("<opensudoku/>", "b.xml").unwrap_err();
+        assert_eq!(error.to_string(), "b.xml:1:1: no `<game>` elements");
+    }
+}
@@ -1,84 +1,396 @@
 //! Game board logic.
 
-use std::fs::read_to_string;
+use std::cell::RefCell;
+use std::fmt;
+use std::fs::write;
+use std::io;
+use std::str::FromStr;
 
-/// Size of game board.
-const SIZE: usize = 9;
+use crate::rules::Rules;
+use crate::sdm::{self, SdmError};
+use crate::solver;
+
+/// Size of the classic game board.
+pub const SIZE: usize = 9;
+
+/// Largest supported board size.
+pub const MAX_SIZE: usize = 25;
+
+/// Shape of the boxes dividing a board.
+///
+/// A board is `size()` cells wide and high, with boxes `box_width` cells wide
+/// and `box_height` cells high, so every row, column and box holds each value
+/// from `1` to `size()` once.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Geometry {
+    /// Width of a box in cells.
+    pub box_width: usize,
+    /// Height of a box in cells.
+    pub box_height: usize,
+}
+
+impl Geometry {
+    /// The classic board of 3x3 boxes.
+    pub const CLASSIC: Geometry = Geometry {
+        box_width: 3,
+        box_height: 3,
+    };
+
+    /// Creates a geometry with boxes of the given shape, or `None` if the
+    /// board would be smaller than 4 or larger than `MAX_SIZE`.
+    pub fn new(box_width: usize, box_height: usize) -> Option<Geometry> {
+        let size = box_width * box_height;
+        if box_width < 2 || box_height < 2 || size > MAX_SIZE {
+            return None;
+        }
+        Some(Geometry {
+            box_width,
+            box_height,
+        })
+    }
+
+    /// Picks the most square boxes for a board of `size`, wider than high,
+    /// e.g. 3x2 for 6. Returns `None` if no such boxes exist.
+    pub fn for_size(size: usize) -> Option<Geometry> {
+        let box_height = (2..)
+            .take_while(|h| h * h <= size)
+            .filter(|&h| size.is_multiple_of(h))
+            .last()?;
+        Geometry::new(size / box_height, box_height)
+    }
+
+    /// Number of cells along each edge of the board, and of values.
+    pub fn size(self) -> usize {
+        self.box_width * self.box_height
+    }
+
+    /// Bit mask with one bit set for every value `1..=size()`.
+    pub fn all_values(self) -> u32 {
+        ((1 << self.size()) - 1) << 1
+    }
+
+    /// Returns the index of the box containing the cell at `ind`.
+    ///
+    /// Boxes are numbered left to right, top to bottom.
+    pub fn section(self, ind: [usize; 2]) -> usize {
+        let boxes_per_row = self.size() / self.box_width;
+        ind[1] / self.box_height * boxes_per_row + ind[0] / self.box_width
+    }
+
+    /// Returns the cell at position `k` of box `sec`, reading left to right,
+    /// top to bottom.
+    pub fn box_cell(self, sec: usize, k: usize) -> [usize; 2] {
+        let boxes_per_row = self.size() / self.box_width;
+        [
+            sec % boxes_per_row * self.box_width + k % self.box_width,
+            sec / boxes_per_row * self.box_height + k / self.box_width,
+        ]
+    }
+}
+
+impl Default for Geometry {
+    fn default() -> Geometry {
+        Geometry::CLASSIC
+    }
+}
+
+/// Writes the box shape as `WxH`, e.g. `3x2`.
+impl fmt::Display for Geometry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}x{}", self.box_width, self.box_height)
+    }
+}
+
+/// Parses a box shape `WxH`, or a board size like `6` for the default boxes
+/// of that size.
+impl FromStr for Geometry {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Geometry, String> {
+        let error = || format!("unsupported board size `{}`", s);
+        let geometry = match s.split_once('x') {
+            Some((w, h)) => {
+                let w = w.parse().map_err(|_| error())?;
+                let h = h.parse().map_err(|_| error())?;
+                Geometry::new(w, h)
+            }
+            None => Geometry::for_size(s.parse().map_err(|_| error())?),
+        };
+        geometry.ok_or_else(error)
+    }
+}
+
+/// Returns the character for `val`: digits up to 9, then letters from `A`.
+pub fn value_char(val: u8) -> Option<char> {
+    match val {
+        1..=9 => Some(char::from(b'0' + val)),
+        10..=35 => Some(char::from(b'A' + val - 10)),
+        _ => None,
+    }
+}
+
+/// Returns the value written as `ch`, the inverse of `value_char`.
+pub fn char_value(ch: char) -> Option<u8> {
+    match ch.to_digit(36)? {
+        0 => None,
+        val => Some(val as u8),
+    }
+}
 
 /// Stores information for a single `Gameboard` cell
 #[derive(Clone, Copy, Debug, Default, PartialEq)]
 pub struct Cell {
+    /// Value of the cell, or 0 when it is empty.
     pub value: u8,
+    /// Whether the value is a given of the puzzle.
     pub loaded: bool,
+    /// Whether the value clashes with another cell.
     pub invalid: bool,
+    /// Pencil marks, with bit `d` set when `d` is noted as a candidate.
+    pub notes: u32,
+}
+
+impl Cell {
+    /// Whether `val` is pencilled in as a candidate.
+    pub fn has_note(&self, val: u8) -> bool {
+        self.notes & (1 << val) != 0
+    }
+}
+
+/// Writes the cell as `value,loaded,invalid,notes`, with flags as `0` or `1`.
+impl fmt::Display for Cell {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{},{},{},{}",
+            self.value, self.loaded as u8, self.invalid as u8, self.notes
+        )
+    }
+}
+
+impl FromStr for Cell {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Cell, String> {
+        let error = || format!("invalid cell `{}`", s);
+        let fields = s
+            .split(',')
+            .map(|field| field.parse::<u32>().map_err(|_| error()))
+            .collect::<Result<Vec<u32>, String>>()?;
+        match fields[..] {
+            [value, loaded, invalid, notes]
+                if value <= MAX_SIZE as u32 && loaded <= 1 && invalid <= 1 =>
+            {
+                Ok(Cell {
+                    value: value as u8,
+                    loaded: loaded == 1,
+                    invalid: invalid == 1,
+                    notes,
+                })
+            }
+            _ => Err(error()),
+        }
+    }
+}
+
+/// How much the game points out wrong values.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Assist {
+    /// Nothing is pointed out.
+    None,
+    /// Values clashing with another cell are flagged.
+    #[default]
+    Conflicts,
+    /// Values differing from the solution are flagged.
+    Solution,
+}
+
+impl Assist {
+    /// Every level, from the least help to the most.
+    pub const ALL: [Assist; 3] =
+        [Assist::None, Assist::Conflicts, Assist::Solution];
+
+    /// The level after this one, wrapping around to `None`.
+    pub fn next(self) -> Assist {
+        let k = Assist::ALL.iter().position(|&a| a == self).unwrap_or(0);
+        Assist::ALL[(k + 1) % Assist::ALL.len()]
+    }
+}
+
+impl fmt::Display for Assist {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Assist::None => "none",
+            Assist::Conflicts => "conflicts",
+            Assist::Solution => "solution",
+        })
+    }
+}
+
+impl FromStr for Assist {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Assist, String> {
+        Assist::ALL
+            .iter()
+            .copied()
+            .find(|assist| assist.to_string() == s)
+            .ok_or_else(|| format!("unknown assist level `{}`", s))
+    }
+}
+
+/// The solution of a board, remembered for the givens and rules it solves.
+///
+/// It is only a cache, so it never makes two boards differ.
+#[derive(Clone, Debug, Default)]
+struct SolutionCache(RefCell<Option<Solved>>);
+
+/// The values solving a puzzle, if any, and the givens and rules it has.
+#[derive(Clone, Debug)]
+struct Solved {
+    puzzle: String,
+    values: Option<Vec<Vec<u8>>>,
+}
+
+impl PartialEq for SolutionCache {
+    fn eq(&self, _: &SolutionCache) -> bool {
+        true
+    }
 }
 
 /// Stores game board information.
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Gameboard {
-    /// Stores the content of the cells. `0` is an empty cell.
-    pub cells: [[Cell; SIZE]; SIZE],
+    /// Shape of the boxes, which also fixes the size of the board.
+    pub geometry: Geometry,
+    /// Stores the content of the cells, indexed by row, then column. `0` is
+    /// an empty cell.
+    pub cells: Vec<Vec<Cell>>,
+    /// Variant rules on top of the rows, columns and boxes.
+    pub rules: Rules,
     /// Whether or not the puzzle is completed
     pub completed: bool,
+    /// Solution found for the current givens and rules.
+    solution: SolutionCache,
 }
 
 impl Gameboard {
-    /// Creates a new game board.
+    /// Creates a new classic game board.
     pub fn new() -> Gameboard {
+        Gameboard::with_geometry(Geometry::CLASSIC)
+    }
+
+    /// Creates a new empty game board with boxes shaped like `geometry`.
+    pub fn with_geometry(geometry: Geometry) -> Gameboard {
+        let size = geometry.size();
         Gameboard {
-            cells: [[Cell::default(); SIZE]; SIZE],
+            geometry,
+            cells: vec![vec![Cell::default(); size]; size],
+            rules: Rules::default(),
             completed: false,
+            solution: SolutionCache::default(),
         }
     }
 
+    /// Number of cells along each edge of the board.
+    pub fn size(&self) -> usize {
+        self.geometry.size()
+    }
+
     /// Load a new game board from the SDM file in `filename`
-    pub fn load_sdm(filename: &str) -> Self {
-        let data = read_to_string(filename).expect("failed to read SDM file");
-        let mut cells = [[Cell::default(); SIZE]; SIZE];
-        let mut row = 0;
-        let mut col = 0;
-        for c in data.chars() {
-            if col == SIZE {
-                col = 0;
-                row += 1;
-            }
-            let value = c.to_digit(10).unwrap() as u8;
-            cells[row][col] = Cell {
-                value,
-                loaded: value != 0,
-                invalid: false,
-            };
-            col += 1;
-        }
-        Self {
-            cells,
-            completed: false,
-        }
+    pub fn load_sdm(filename: &str) -> Result<Self, SdmError> {
+        sdm::load(filename)
+    }
+
+    /// Formats the loaded cells as a single SDM line, `0` marking blanks.
+    ///
+    /// Values above 9 are written as letters.
+    pub fn to_sdm(&self) -> String {
+        self.cells
+            .iter()
+            .flatten()
+            .map(|cell| if cell.loaded { cell.value } else { 0 })
+            .map(|value| value_char(value).unwrap_or('0'))
+            .collect()
+    }
+
+    /// Save the loaded cells to the SDM file in `filename`
+    pub fn save_sdm(&self, filename: &str) -> io::Result<()> {
+        write(filename, self.to_sdm())
+    }
+
+    /// The values of all cells, indexed by row then column.
+    pub fn values(&self) -> Vec<Vec<u8>> {
+        let row = |row: &Vec<Cell>| row.iter().map(|cell| cell.value).collect();
+        self.cells.iter().map(row).collect()
+    }
+
+    /// Cells that may not hold the same value as `ind`, under the classic
+    /// constraints and the variant rules.
+    pub fn peers(&self, ind: [usize; 2]) -> Vec<[usize; 2]> {
+        self.rules.peers(self.geometry, ind)
     }
 
     /// Gets the character at cell location.
     pub fn char(&self, ind: [usize; 2]) -> Option<char> {
-        Some(match self.cells[ind[1]][ind[0]].value {
-            1 => '1',
-            2 => '2',
-            3 => '3',
-            4 => '4',
-            5 => '5',
-            6 => '6',
-            7 => '7',
-            8 => '8',
-            9 => '9',
-            _ => return None,
-        })
+        value_char(self.cells[ind[1]][ind[0]].value)
     }
 
     /// Set cell value.
     pub fn set(&mut self, ind: [usize; 2], val: u8) {
         if !self.cells[ind[1]][ind[0]].loaded {
-            self.validate(ind, val);
             self.cells[ind[1]][ind[0]].value = val;
+            self.update_conflicts();
+        }
+        self.update_completed();
+    }
+
+    /// The values solving the puzzle given by the loaded cells, indexed by
+    /// row then column, or `None` if it has no solution.
+    ///
+    /// The puzzle is solved once and the solution kept until the givens or
+    /// the rules change.
+    pub fn solution(&self) -> Option<Vec<Vec<u8>>> {
+        self.with_solution(|solution| solution.cloned())
+    }
+
+    /// Whether the value entered at `ind` differs from the solution. Givens,
+    /// empty cells and puzzles without a solution are never wrong.
+    pub fn is_wrong(&self, ind: [usize; 2]) -> bool {
+        let cell = self.cells[ind[1]][ind[0]];
+        !cell.loaded
+            && cell.value != 0
+            && self.with_solution(|solution| {
+                solution.is_some_and(|rows| rows[ind[1]][ind[0]] != cell.value)
+            })
+    }
+
+    /// Whether the value at `ind` is pointed out as wrong with `assist`.
+    pub fn flagged(&self, ind: [usize; 2], assist: Assist) -> bool {
+        match assist {
+            Assist::None => false,
+            Assist::Conflicts => self.cells[ind[1]][ind[0]].invalid,
+            Assist::Solution => self.is_wrong(ind),
         }
-        // check for puzzle completion
+    }
+
+    /// Calls `f` with the solution, solving the puzzle first if the givens
+    /// or rules changed since it was last solved.
+    fn with_solution<T>(
+        &self,
+        f: impl FnOnce(Option<&Vec<Vec<u8>>>) -> T,
+    ) -> T {
+        let puzzle = format!("{}\n{}", self.to_sdm(), self.rules);
+        let mut cache = self.solution.0.borrow_mut();
+        if cache.as_ref().map(|solved| &solved.puzzle) != Some(&puzzle) {
+            let values = solver::solve(self).map(|solved| solved.values());
+            *cache = Some(Solved { puzzle, values });
+        }
+        f(cache.as_ref().and_then(|solved| solved.values.as_ref()))
+    }
+
+    /// check for puzzle completion
+    pub fn update_completed(&mut self) {
         self.completed = self
             .cells
             .iter()
@@ -86,58 +398,66 @@ impl Gameboard {
             .all(|cell| !cell.invalid && cell.value != 0);
     }
 
-    /// validate the `val` to be put into `ind`
-    fn validate(&mut self, ind: [usize; 2], val: u8) {
-        let [b, a] = ind;
-        if val == 0 {
-            self.cells[a][b].invalid = false;
-            return;
+    /// Toggle the pencil mark for `val` in an empty cell.
+    pub fn toggle_note(&mut self, ind: [usize; 2], val: u8) {
+        let size = self.size();
+        let cell = &mut self.cells[ind[1]][ind[0]];
+        if !cell.loaded && cell.value == 0 && val != 0 && val as usize <= size {
+            cell.notes ^= 1 << val;
         }
-        // check row
-        for i in 0..SIZE {
-            if i == a {
-                continue;
-            }
-            if self.cells[a][i].value == val {
-                self.cells[a][b].invalid = true;
-                return;
-            }
-        }
-        // check col
-        for i in 0..SIZE {
-            if i == b {
-                continue;
-            }
-            if self.cells[i][b].value == val {
-                self.cells[a][b].invalid = true;
-                return;
-            }
+    }
+
+    /// Clear the pencil marks for `val` from the peers of `ind`.
+    pub fn clear_peer_notes(&mut self, ind: [usize; 2], val: u8) {
+        for [j, i] in self.peers(ind) {
+            self.cells[i][j].notes &= !(1 << val);
         }
-        // check box
-        let (row, col) = (a / 3, b / 3);
-        for i in 3 * row..3 * row + 3 {
-            for j in 3 * col..3 * col + 3 {
-                if i == a && j == b {
-                    continue;
-                }
-                if self.cells[i][j].value == val {
-                    self.cells[a][b].invalid = true;
-                    return;
-                }
+    }
+
+    /// Flags every cell whose value clashes with a peer or breaks a variant
+    /// rule, and clears the flag on all others.
+    pub fn update_conflicts(&mut self) {
+        let values = self.values();
+        let layout = self.rules.layout(self.geometry);
+        for (i, row) in self.cells.iter_mut().enumerate() {
+            for (j, cell) in row.iter_mut().enumerate() {
+                let val = cell.value;
+                let units = layout.cell_units[i][j].iter();
+                let mut peers = units
+                    .flat_map(|&n| &layout.units[n])
+                    .chain(&layout.peers[i][j])
+                    .filter(|&&peer| peer != [j, i]);
+                cell.invalid = val != 0
+                    && (peers.any(|&[b, a]| values[a][b] == val)
+                        || !self.rules.allows(&values, [j, i], val));
             }
         }
-        self.cells[a][b].invalid = false;
     }
 
-    /// Build a `Gameboard` from an array of `Cell` values
-    pub fn from_cells(cells: [[u8; SIZE]; SIZE]) -> Gameboard {
-        let mut ret = Gameboard::new();
-        for (i, row) in cells.iter().enumerate() {
-            for (j, &col) in row.iter().enumerate() {
+    /// Build a `Gameboard` from rows of values, picking the box shape from
+    /// the number of rows with `Geometry::for_size`.
+    ///
+    /// Panics if no boxes fit a board of that size.
+    pub fn from_cells<R: AsRef<[u8]>>(cells: impl AsRef<[R]>) -> Gameboard {
+        let size = cells.as_ref().len();
+        let geometry = Geometry::for_size(size)
+            .unwrap_or_else(|| panic!("unsupported board size {}", size));
+        Gameboard::from_values(geometry, cells)
+    }
+
+    /// Build a `Gameboard` with boxes shaped like `geometry` from rows of
+    /// values, each `0` or a given.
+    pub fn from_values<R: AsRef<[u8]>>(
+        geometry: Geometry,
+        cells: impl AsRef<[R]>,
+    ) -> Gameboard {
+        let mut ret = Gameboard::with_geometry(geometry);
+        for (i, row) in cells.as_ref().iter().enumerate() {
+            for (j, &col) in row.as_ref().iter().enumerate() {
                 ret.cells[i][j] = Cell {
                     value: col,
                     loaded: col != 0,
-                    invalid: false,
+                    ..Cell::default()
                 };
             }
         }
@@ -145,13 +465,21 @@ impl Gameboard {
     }
 }
 
+impl Default for Gameboard {
+    fn default() -> Gameboard {
+        Gameboard::new()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::generator::Rng;
+    use crate::solver::solve;
 
     #[test]
     fn load_sdm() {
-        let got = Gameboard::load_sdm("puzzles/puzzle.sdm");
+        let got = Gameboard::load_sdm("static/puzzle.sdm").unwrap();
         let want = Gameboard::from_cells([
             [0, 1, 6, 4, 0, 0, 0, 0, 0],
             [2, 0, 0, 0, 0, 9, 0, 0, 0],
@@ -165,4 +493,213 @@ mod tests {
         ]);
         assert_eq!(got, want);
     }
+
+    #[test]
+    fn to_sdm() {
+        let mut cells = [[0; SIZE]; SIZE];
+        cells[0][1] = 1;
+        cells[8][7] = 9;
+        let mut gameboard = Gameboard::from_cells(cells);
+        gameboard.set([0, 0], 5);
+        let mut want = "0".repeat(SIZE * SIZE);
+        want.replace_range(1..2, "1");
+        want.replace_range(79..80, "9");
+        assert_eq!(gameboard.to_sdm(), want);
+    }
+
+    #[test]
+    fn notes() {
+        let mut cells = [[0; SIZE]; SIZE];
+        cells[0][0] = 1;
+        let mut gameboard = Gameboard::from_cells(cells);
+        gameboard.toggle_note([0, 0], 2);
+        gameboard.toggle_note([1, 0], 2);
+        gameboard.toggle_note([1, 0], 3);
+        gameboard.toggle_note([1, 0], 3);
+        gameboard.toggle_note([4, 4], 2);
+        assert_eq!(gameboard.cells[0][0].notes, 0);
+        assert!(gameboard.cells[0][1].has_note(2));
+        assert!(!gameboard.cells[0][1].has_note(3));
+
+        gameboard.set([8, 0], 2);
+        gameboard.clear_peer_notes([8, 0], 2);
+        assert!(!gameboard.cells[0][1].has_note(2));
+        assert!(gameboard.cells[4][4].has_note(2));
+    }
+
+    #[test]
+    fn geometries() {
+        let sizes = [(4, "2x2"), (6, "3x2"), (12, "4x3"), (16, "4x4")];
+        for &(size, shape) in &sizes {
+            let geometry = Geometry::for_size(size).unwrap();
+            assert_eq!(geometry.to_string(), shape);
+            assert_eq!(size.to_string().parse(), Ok(geometry));
+        }
+        assert_eq!("2x3".parse::<Geometry>().unwrap().size(), 6);
+        assert_eq!(Geometry::for_size(7), None);
+        assert!("6x6".parse::<Geometry>().is_err());
+
+        // Rows 2 and 3 of a 6x6 board start the second band of 3x2 boxes.
+        let geometry = Geometry::for_size(6).unwrap();
+        assert_eq!(geometry.section([4, 1]), 1);
+        assert_eq!(geometry.section([0, 2]), 2);
+        assert_eq!(geometry.box_cell(3, 5), [5, 3]);
+        let peers = Gameboard::with_geometry(geometry).peers([0, 0]);
+        assert!(peers.contains(&[2, 1]));
+        assert!(peers.contains(&[0, 5]));
+        assert!(!peers.contains(&[3, 1]));
+    }
+
+    #[test]
+    fn variant_conflicts() {
+        let mut gameboard = Gameboard::new();
+        let rules = "anti-knight\ncage 5 r5c5 r5c6";
+        gameboard.rules = Rules::parse(rules, gameboard.geometry).unwrap();
+        gameboard.toggle_note([1, 2], 5);
+        gameboard.set([0, 0], 5);
+        gameboard.clear_peer_notes([0, 0], 5);
+        assert!(!gameboard.cells[2][1].has_note(5));
+        gameboard.set([1, 2], 5);
+        assert!(gameboard.cells[2][1].invalid);
+
+        gameboard.set([4, 4], 4);
+        assert!(!gameboard.cells[4][4].invalid);
+        gameboard.set([5, 4], 3);
+        assert!(gameboard.cells[4][5].invalid);
+        gameboard.set([5, 4], 1);
+        assert!(!gameboard.cells[4][5].invalid);
+    }
+
+    /// Finds the cells in a clash the slow way, comparing every pair.
+    fn clashes(gameboard: &Gameboard) -> Vec<Vec<bool>> {
+        let geometry = gameboard.geometry;
+        let size = geometry.size();
+        let values = gameboard.values();
+        let clash = |ind: [usize; 2]| {
+            let [j, i] = ind;
+            let val = values[i][j];
+            let rule_peers = gameboard.peers(ind);
+            let peers = (0..size * size).map(|k| [k % size, k / size]);
+            let mut peers = peers.filter(|&[b, a]| {
+                let classic = a == i
+                    || b == j
+                    || !gameboard.rules.replaces_boxes()
+                        && geometry.section([b, a]) == geometry.section(ind);
+                [b, a] != ind && (classic || rule_peers.contains(&[b, a]))
+            });
+            val != 0
+                && (peers.any(|[b, a]| values[a][b] == val)
+                    || !gameboard.rules.allows(&values, ind, val))
+        };
+        let row = |i| (0..size).map(|j| clash([j, i])).collect();
+        (0..size).map(row).collect()
+    }
+
+    #[test]
+    fn conflicts_follow_random_edits() {
+        let variants = ["", "diagonals\nanti-king\ncage 12 r1c1 r1c2 r2c1"];
+        for seed in 0..60 {
+            let mut rng = Rng::new(seed);
+            let shape = ["2x2", "3x2", "3x3"][rng.below(3)];
+            let mut empty = Gameboard::with_geometry(shape.parse().unwrap());
+            if shape == "3x3" {
+                let rules = variants[rng.below(2)];
+                empty.rules = Rules::parse(rules, empty.geometry).unwrap();
+            }
+            let solution = solve(&empty).unwrap().values();
+            let size = empty.size();
+
+            // Give half the cells, then enter the right value about half the
+            // time and anything else otherwise.
+            let mut gameboard = empty.clone();
+            for (row, values) in gameboard.cells.iter_mut().zip(&solution) {
+                for (cell, &value) in row.iter_mut().zip(values) {
+                    if rng.below(2) == 0 {
+                        cell.value = value;
+                        cell.loaded = true;
+                    }
+                }
+            }
+            for _ in 0..100 {
+                let [j, i] = [rng.below(size), rng.below(size)];
+                let val = match rng.below(2) {
+                    0 => solution[i][j],
+                    _ => rng.below(size + 1) as u8,
+                };
+                gameboard.set([j, i], val);
+
+                let flags: Vec<Vec<bool>> = gameboard
+                    .cells
+                    .iter()
+                    .map(|row| row.iter().map(|cell| cell.invalid).collect())
+                    .collect();
+                let clashes = clashes(&gameboard);
+                assert_eq!(flags, clashes, "seed {}", seed);
+                let full = gameboard.values().iter().flatten().all(|&v| v != 0);
+                let clean = clashes.iter().flatten().all(|&clash| !clash);
+                assert_eq!(gameboard.completed, full && clean, "seed {}", seed);
+            }
+        }
+    }
+
+    #[test]
+    fn clears_fixed_conflicts() {
+        let mut gameboard = Gameboard::new();
+        gameboard.set([0, 0], 5);
+        gameboard.set([8, 0], 5);
+        assert!(gameboard.cells[0][0].invalid);
+        assert!(gameboard.cells[0][8].invalid);
+        gameboard.set([0, 0], 4);
+        assert!(!gameboard.cells[0][0].invalid);
+        assert!(!gameboard.cells[0][8].invalid);
+    }
+
+    #[test]
+    fn large_values() {
+        let mut gameboard = Gameboard::with_geometry("4x4".parse().unwrap());
+        gameboard.set([0, 0], 16);
+        gameboard.set([15, 0], 16);
+        assert_eq!(gameboard.char([0, 0]), Some('G'));
+        assert!(gameboard.cells[0][15].invalid);
+        assert_eq!(char_value('g'), Some(16));
+        assert_eq!(char_value('0'), None);
+        assert_eq!(value_char(char_value('P').unwrap()), Some('P'));
+    }
+
+    #[test]
+    fn checks_against_solution() {
+        let mut gameboard = Gameboard::load_sdm("static/puzzle.sdm").unwrap();
+        let unsolved = gameboard.clone();
+        let solution = gameboard.solution().expect("puzzle has a solution");
+        assert_eq!(solution[0], [3, 1, 6, 4, 5, 2, 9, 7, 8]);
+        // Remembering the solution leaves the board as it was.
+        assert_eq!(gameboard, unsolved);
+
+        // 5 is wrong without clashing with another cell.
+        gameboard.set([0, 0], 5);
+        assert!(gameboard.is_wrong([0, 0]));
+        assert!(!gameboard.flagged([0, 0], Assist::None));
+        assert!(!gameboard.flagged([0, 0], Assist::Conflicts));
+        assert!(gameboard.flagged([0, 0], Assist::Solution));
+        gameboard.set([0, 0], 3);
+        assert!(!gameboard.is_wrong([0, 0]));
+        assert!(!gameboard.is_wrong([1, 0]));
+
+        // Changing the rules solves the puzzle again.
+        let rules = "cage 2 r1c1 r1c5";
+        gameboard.rules = Rules::parse(rules, gameboard.geometry).unwrap();
+        assert_eq!(gameboard.solution(), None);
+        assert!(!gameboard.is_wrong([0, 0]));
+    }
+
+    #[test]
+    fn assist_levels() {
+        assert_eq!("solution".parse(), Ok(Assist::Solution));
+        assert_eq!(
+            "all".parse::<Assist>(),
+            Err("unknown assist level `all`".into())
+        );
+        let levels: Vec<_> = Assist::ALL.iter().map(|a| a.next()).collect();
+        assert_eq!(levels, [Assist::Conflicts, Assist::Solution, Assist::None]);
+    }
 }
@@ -1,8 +1,15 @@
 //! Gameboard controller.
 
 use piston::GenericEvent;
+use std::time::{Duration, Instant};
 
-use crate::Gameboard;
+use crate::gameboard::{char_value, Assist};
+use crate::grader::{grade, hint, Hint};
+use crate::history::History;
+use crate::keymap::{Action, Chord, KeyMap};
+use crate::save::SavedGame;
+use crate::stats::{GameStats, Stats};
+use crate::{Gameboard, Geometry};
 
 /// Handles events for Sudoku game.
 pub struct GameboardController {
@@ -10,56 +17,800 @@ pub struct GameboardController {
     pub gameboard: Gameboard,
     /// Selected cell.
     pub selected_cell: Option<[usize; 2]>,
+    /// Hint currently shown to the player.
+    pub hint: Option<Hint>,
+    /// Whether the technique behind the hint is explained.
+    pub hint_explained: bool,
+    /// Number of hints given so far.
+    pub hints_used: u32,
+    /// Number of wrong values entered: values clashing with another cell, or
+    /// differing from the solution when that is checked.
+    pub mistakes: u32,
+    /// How much wrong values are pointed out.
+    pub assist: Assist,
+    /// Number of mistakes ending the game, if any.
+    pub mistake_limit: Option<u32>,
+    /// Whether digits are entered as pencil marks.
+    pub notes_mode: bool,
+    /// Whether placing a digit removes it from the pencil marks of peers.
+    pub auto_clear_notes: bool,
+    /// Whether the peers of the selected cell and the cells sharing its
+    /// value are highlighted.
+    pub highlight: bool,
+    /// Index of the theme in use, moved on by the theme key. Wrapping it
+    /// around the list of themes is left to the caller.
+    pub theme: usize,
+    /// Moves that can be undone and redone.
+    pub history: History,
+    /// Actions bound to keys.
+    pub keymap: KeyMap,
+    /// Finished games, including this one once it is completed.
+    pub stats: Stats,
+    /// Whether the statistics are shown instead of the board.
+    pub show_stats: bool,
+    /// Time played up to `last_event`.
+    elapsed: Duration,
+    /// When the last event was handled.
+    last_event: Instant,
+    /// Whether the window has focus, keeping the clock running.
+    focused: bool,
+    /// Whether the completed game has been added to `stats`.
+    recorded: bool,
     /// Stores last mouse cursor position.
     cursor_pos: [f64; 2],
+    /// Value of the number pad button pressed and not yet released, with 0
+    /// for the clear button.
+    dragged: Option<u8>,
+    /// Cell whose value was last stepped by the mouse wheel, judged once
+    /// the player moves on.
+    scrolled: Option<[usize; 2]>,
+    /// Whether a shift key is held, entering pencil marks.
+    shift_held: bool,
+    /// Whether a control key is held.
+    ctrl_held: bool,
 }
 
 impl GameboardController {
     /// Creates a new gameboard controller.
     pub fn new(gameboard: Gameboard) -> GameboardController {
+        let recorded = gameboard.completed;
         GameboardController {
             gameboard,
             selected_cell: None,
+            hint: None,
+            hint_explained: false,
+            hints_used: 0,
+            mistakes: 0,
+            assist: Assist::default(),
+            mistake_limit: None,
+            notes_mode: false,
+            auto_clear_notes: true,
+            highlight: true,
+            theme: 0,
+            history: History::new(),
+            keymap: KeyMap::new(),
+            stats: Stats::default(),
+            show_stats: false,
+            recorded,
+            elapsed: Duration::ZERO,
+            last_event: Instant::now(),
+            focused: true,
             cursor_pos: [0.0; 2],
+            dragged: None,
+            scrolled: None,
+            shift_held: false,
+            ctrl_held: false,
         }
     }
 
+    /// Creates a controller continuing a saved game.
+    pub fn resume(saved: SavedGame) -> GameboardController {
+        GameboardController {
+            history: saved.history,
+            elapsed: saved.elapsed,
+            mistakes: saved.mistakes,
+            assist: saved.assist,
+            mistake_limit: saved.mistake_limit,
+            hints_used: saved.hints,
+            ..GameboardController::new(saved.gameboard)
+        }
+    }
+
+    /// Captures the game so it can be resumed later.
+    pub fn saved_game(&self) -> SavedGame {
+        SavedGame {
+            gameboard: self.gameboard.clone(),
+            history: self.history.clone(),
+            elapsed: self.elapsed(),
+            mistakes: self.mistakes,
+            hints: self.hints_used,
+            assist: self.assist,
+            mistake_limit: self.mistake_limit,
+        }
+    }
+
+    /// Time spent on the puzzle, stopping once the game is over and while
+    /// the window is out of focus.
+    pub fn elapsed(&self) -> Duration {
+        if self.game_over() || self.paused() {
+            self.elapsed
+        } else {
+            self.elapsed + self.last_event.elapsed()
+        }
+    }
+
+    /// Whether the clock is stopped because the window lost focus.
+    pub fn paused(&self) -> bool {
+        !self.focused && !self.game_over()
+    }
+
+    /// Whether the limit on mistakes has been reached before completing the
+    /// puzzle.
+    pub fn lost(&self) -> bool {
+        !self.gameboard.completed
+            && self
+                .mistake_limit
+                .is_some_and(|limit| self.mistakes >= limit)
+    }
+
+    /// Whether the puzzle is completed or lost, stopping the clock.
+    pub fn game_over(&self) -> bool {
+        self.gameboard.completed || self.lost()
+    }
+
+    /// The statistics of this game, once it is completed.
+    pub fn finished_game(&self) -> Option<&GameStats> {
+        self.stats.games.last().filter(|_| self.recorded)
+    }
+
     /// Handles events.
     pub fn event<E: GenericEvent>(&mut self, pos: [f64; 2], size: f64, e: &E) {
         use piston::input::{Button, Key, MouseButton};
 
+        let n = self.gameboard.size();
+        self.elapsed = self.elapsed();
+        self.last_event = Instant::now();
+        if let Some(focused) = e.focus_args() {
+            self.focused = focused;
+        }
         if let Some(pos) = e.mouse_cursor_args() {
             self.cursor_pos = pos;
         }
-        if let Some(Button::Mouse(MouseButton::Left)) = e.press_args() {
-            // Find coordinates relative to upper left corner.
-            let x = self.cursor_pos[0] - pos[0];
-            let y = self.cursor_pos[1] - pos[1];
-            // Check that coordinates are inside board boundaries.
-            if x >= 0.0 && x <= size && y >= 0.0 && y <= size {
-                // Compute the cell position.
-                let cell_x = (x / size * 9.0) as usize;
-                let cell_y = (y / size * 9.0) as usize;
-                self.selected_cell = Some([cell_x, cell_y]);
+        let board = grid_index(pos, size / n as f64, [n, n], self.cursor_pos);
+        let pad = NumberPad::new(pos, size, self.gameboard.geometry)
+            .value_at(self.cursor_pos);
+        if e.press_args().is_some() {
+            self.settle_scrolled();
+        }
+        match e.press_args() {
+            Some(Button::Mouse(MouseButton::Left)) => {
+                if board.is_some() {
+                    self.selected_cell = board;
+                }
+                self.dragged = pad;
+            }
+            Some(Button::Mouse(MouseButton::Right)) => {
+                if let (Some(ind), Some(val)) = (self.selected_cell, pad) {
+                    if val != 0 {
+                        self.toggle_note(ind, val);
+                    }
+                }
+            }
+            _ => {}
+        }
+        if let Some(Button::Mouse(MouseButton::Left)) = e.release_args() {
+            // A digit is placed when its button is clicked, or dragged from
+            // the number pad and dropped on a cell.
+            if let Some(val) = self.dragged.take() {
+                if board.is_some() {
+                    self.selected_cell = board;
+                }
+                if board.is_some() || pad == Some(val) {
+                    self.act(match val {
+                        0 => Action::Clear,
+                        val => Action::Value(val),
+                    });
+                }
+            }
+        }
+        if let Some([_, ticks]) = e.mouse_scroll_args() {
+            if ticks != 0.0 {
+                self.cycle_value(ticks.signum() as i32);
+            }
+        }
+        if let Some(Button::Keyboard(key)) = e.release_args() {
+            match key {
+                Key::LShift | Key::RShift => self.shift_held = false,
+                Key::LCtrl | Key::RCtrl => self.ctrl_held = false,
+                _ => {}
             }
         }
         if let Some(Button::Keyboard(key)) = e.press_args() {
-            if let Some(ind) = self.selected_cell {
-                // Set cell value.
-                match key {
-                    Key::D1 => self.gameboard.set(ind, 1),
-                    Key::D2 => self.gameboard.set(ind, 2),
-                    Key::D3 => self.gameboard.set(ind, 3),
-                    Key::D4 => self.gameboard.set(ind, 4),
-                    Key::D5 => self.gameboard.set(ind, 5),
-                    Key::D6 => self.gameboard.set(ind, 6),
-                    Key::D7 => self.gameboard.set(ind, 7),
-                    Key::D8 => self.gameboard.set(ind, 8),
-                    Key::D9 => self.gameboard.set(ind, 9),
-                    Key::Backspace => self.gameboard.set(ind, 0),
-                    _ => {}
+            match key {
+                Key::LShift | Key::RShift => self.shift_held = true,
+                Key::LCtrl | Key::RCtrl => self.ctrl_held = true,
+                _ => {}
+            }
+            // Letters for values above 9 win over bindings on the same key,
+            // which stay available with control held.
+            let chord = Chord {
+                ctrl: self.ctrl_held,
+                shift: self.shift_held,
+                key,
+            };
+            let action = match self.letter_value(key) {
+                Some(val) if !self.ctrl_held => Some(Action::Value(val)),
+                Some(_) => self.keymap.action(chord).or_else(|| {
+                    self.keymap.action(Chord {
+                        ctrl: false,
+                        ..chord
+                    })
+                }),
+                None => self.keymap.action(chord),
+            };
+            if let Some(action) = action {
+                self.act(action);
+            }
+        }
+    }
+
+    /// The value above 9 a letter key enters on this board, if any.
+    fn letter_value(&self, key: piston::input::Key) -> Option<u8> {
+        let val = std::char::from_u32(key as u32).and_then(char_value)?;
+        Some(val)
+            .filter(|&val| val > 9 && val as usize <= self.gameboard.size())
+    }
+
+    /// Carries out `action`.
+    pub fn act(&mut self, action: Action) {
+        self.settle_scrolled();
+        match action {
+            Action::Up => self.move_selection([0, -1]),
+            Action::Down => self.move_selection([0, 1]),
+            Action::Left => self.move_selection([-1, 0]),
+            Action::Right => self.move_selection([1, 0]),
+            Action::NextEmpty => self.select_next_empty(),
+            Action::Value(val) if val as usize > self.gameboard.size() => {}
+            Action::Value(val) => {
+                if let Some(ind) = self.selected_cell {
+                    if self.taking_notes() {
+                        self.toggle_note(ind, val)
+                    } else {
+                        self.set(ind, val)
+                    }
                 }
             }
+            Action::Clear => {
+                if let Some(ind) = self.selected_cell {
+                    self.set(ind, 0)
+                }
+            }
+            Action::Hint => self.show_hint(),
+            Action::Notes => self.notes_mode = !self.notes_mode,
+            Action::Highlight => self.highlight = !self.highlight,
+            Action::Theme => self.theme += 1,
+            Action::Stats => self.show_stats = !self.show_stats,
+            Action::Assist => self.assist = self.assist.next(),
+            Action::Undo => self.undo(),
+            Action::Redo => self.redo(),
+        }
+    }
+
+    /// Moves the selection by `step` cells, wrapping around the edges of
+    /// the board, or selects the top left cell if none is.
+    fn move_selection(&mut self, step: [isize; 2]) {
+        let size = self.gameboard.size() as isize;
+        self.selected_cell = Some(match self.selected_cell {
+            Some([x, y]) => [
+                (x as isize + step[0]).rem_euclid(size) as usize,
+                (y as isize + step[1]).rem_euclid(size) as usize,
+            ],
+            None => [0, 0],
+        });
+    }
+
+    /// Selects the first empty cell after the selected one in reading
+    /// order, wrapping around to the top. Stays put if the board is full.
+    fn select_next_empty(&mut self) {
+        let size = self.gameboard.size();
+        let start = match self.selected_cell {
+            Some([x, y]) => y * size + x,
+            None => size * size - 1,
+        };
+        let cells = &self.gameboard.cells;
+        let next = (1..=size * size)
+            .map(|k| (start + k) % (size * size))
+            .map(|k| [k % size, k / size])
+            .find(|&[x, y]| cells[y][x].value == 0);
+        if next.is_some() {
+            self.selected_cell = next;
+        }
+    }
+
+    /// Whether digits currently go into the pencil marks.
+    pub fn taking_notes(&self) -> bool {
+        self.notes_mode || self.shift_held
+    }
+
+    /// Sets a cell value, counting it as a mistake if it clashes, or if it
+    /// differs from the solution when that is checked.
+    fn set(&mut self, ind: [usize; 2], val: u8) {
+        if self.place(ind, val) {
+            self.judge(ind);
+        }
+    }
+
+    /// Counts the value of a cell as a mistake if it clashes, or if it
+    /// differs from the solution when that is checked.
+    fn judge(&mut self, ind: [usize; 2]) {
+        if self.gameboard.cells[ind[1]][ind[0]].value == 0 {
+            return;
+        }
+        let mistake = match self.assist {
+            Assist::Solution => self.gameboard.is_wrong(ind),
+            _ => self.gameboard.cells[ind[1]][ind[0]].invalid,
+        };
+        if mistake {
+            self.mistakes += 1;
+            if self.lost() {
+                self.stop_clock();
+            }
+        }
+    }
+
+    /// Sets a cell value unless it is given or the game is lost, tidying up
+    /// notes and hints that it settles. Returns whether the value was set.
+    fn place(&mut self, ind: [usize; 2], val: u8) -> bool {
+        if self.lost() || self.gameboard.cells[ind[1]][ind[0]].loaded {
+            return false;
+        }
+        let before = self.gameboard.clone();
+        self.gameboard.set(ind, val);
+        if self.auto_clear_notes && val != 0 {
+            self.gameboard.clear_peer_notes(ind, val);
+        }
+        self.history.record(&before, &self.gameboard);
+        if self.hint.as_ref().map(|hint| hint.cell) == Some(ind) {
+            self.hint = None;
+        }
+        self.record_completed();
+        true
+    }
+
+    /// Steps the value of the selected cell up or down through the values
+    /// and empty, as the mouse wheel does. Only the value left in the cell
+    /// can count as a mistake, once the player moves on.
+    fn cycle_value(&mut self, step: i32) {
+        if let Some(ind) = self.selected_cell {
+            let values = self.gameboard.size() as i32 + 1;
+            let val = self.gameboard.cells[ind[1]][ind[0]].value as i32;
+            if self.place(ind, (val + step).rem_euclid(values) as u8) {
+                self.scrolled = Some(ind);
+            }
+        }
+    }
+
+    /// Judges the value the mouse wheel left in a cell, if any.
+    fn settle_scrolled(&mut self) {
+        if let Some(ind) = self.scrolled.take() {
+            self.judge(ind);
+        }
+    }
+
+    /// Toggles a pencil mark, recording it in the history.
+    fn toggle_note(&mut self, ind: [usize; 2], val: u8) {
+        if self.lost() {
+            return;
+        }
+        let before = self.gameboard.clone();
+        self.gameboard.toggle_note(ind, val);
+        self.history.record(&before, &self.gameboard);
+    }
+
+    /// Reverts the last move, unless the game is lost.
+    pub fn undo(&mut self) {
+        if self.lost() {
+            return;
+        }
+        self.history.undo(&mut self.gameboard);
+        self.record_completed();
+    }
+
+    /// Repeats the last undone move, unless the game is lost.
+    pub fn redo(&mut self) {
+        if self.lost() {
+            return;
+        }
+        self.history.redo(&mut self.gameboard);
+        self.record_completed();
+    }
+
+    /// Adds the game to the statistics the first time it is completed.
+    fn record_completed(&mut self) {
+        if !self.gameboard.completed || self.recorded {
+            return;
+        }
+        self.stop_clock();
+        self.recorded = true;
+        self.stats.record(GameStats {
+            puzzle: self.gameboard.to_sdm(),
+            difficulty: grade(&self.gameboard).difficulty(),
+            elapsed: self.elapsed,
+            mistakes: self.mistakes,
+            hints: self.hints_used,
+        });
+    }
+
+    /// Stops the clock at the move ending the game.
+    fn stop_clock(&mut self) {
+        if self.focused {
+            self.elapsed += self.last_event.elapsed();
+            self.last_event = Instant::now();
+        }
+    }
+
+    /// Highlights the next deducible cell, or explains the current hint.
+    fn show_hint(&mut self) {
+        if self.lost() {
+            return;
+        }
+        if self.hint.is_some() {
+            self.hint_explained = true;
+        } else if let Some(hint) = hint(&self.gameboard) {
+            self.hint = Some(hint);
+            self.hint_explained = false;
+            self.hints_used += 1;
+        }
+    }
+}
+
+/// Which cell of a grid `dims` cells wide and high, with cells of
+/// `cell_size` and its top left corner at `origin`, contains `point`.
+pub fn grid_index(
+    origin: [f64; 2],
+    cell_size: f64,
+    dims: [usize; 2],
+    point: [f64; 2],
+) -> Option<[usize; 2]> {
+    // Find coordinates relative to upper left corner.
+    let x = point[0] - origin[0];
+    let y = point[1] - origin[1];
+    let width = dims[0] as f64 * cell_size;
+    let height = dims[1] as f64 * cell_size;
+    // Check that coordinates are inside grid boundaries.
+    if x >= 0.0 && x < width && y >= 0.0 && y < height {
+        Some([(x / cell_size) as usize, (y / cell_size) as usize])
+    } else {
+        None
+    }
+}
+
+/// Layout of the on-screen number pad beside the board.
+///
+/// The values are laid out in a grid shaped like a box, as pencil marks are,
+/// with a clear button across the row below them.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct NumberPad {
+    /// Position of the top left button.
+    pub position: [f64; 2],
+    /// Size of a button along both edges.
+    pub button_size: f64,
+    /// Number of buttons across.
+    pub columns: usize,
+    /// Number of rows of buttons, including the clear button.
+    pub rows: usize,
+}
+
+impl NumberPad {
+    /// Width of the pad as a share of the board size, with the buttons of
+    /// boxes narrower than three cells kept as wide as for three.
+    const WIDTH: f64 = 0.24;
+
+    /// Lays out the number pad for a board at `pos` of `size`, to the right
+    /// of it and level with its bottom edge.
+    pub fn new(pos: [f64; 2], size: f64, geometry: Geometry) -> NumberPad {
+        let columns = geometry.box_width;
+        let rows = geometry.box_height + 1;
+        let button_size = size * NumberPad::WIDTH / columns.max(3) as f64;
+        NumberPad {
+            position: [
+                pos[0] + size + button_size / 2.0,
+                pos[1] + size - rows as f64 * button_size,
+            ],
+            button_size,
+            columns,
+            rows,
+        }
+    }
+
+    /// Widest the pad and the gap before it get beside a board of `size`,
+    /// whatever its geometry.
+    pub fn room(size: f64) -> f64 {
+        let widest_button = size * NumberPad::WIDTH / 3.0;
+        size * NumberPad::WIDTH + widest_button / 2.0
+    }
+
+    /// Rectangle of the button for `val`, or of the clear button for 0.
+    pub fn button(&self, val: u8) -> [f64; 4] {
+        let [x, y] = self.position;
+        let size = self.button_size;
+        if val == 0 {
+            let top = y + (self.rows - 1) as f64 * size;
+            return [x, top, self.columns as f64 * size, size];
+        }
+        let k = val as usize - 1;
+        let (col, row) = (k % self.columns, k / self.columns);
+        [x + col as f64 * size, y + row as f64 * size, size, size]
+    }
+
+    /// Value of the button under `point`, with 0 for the clear button.
+    pub fn value_at(&self, point: [f64; 2]) -> Option<u8> {
+        let dims = [self.columns, self.rows];
+        let [col, row] =
+            grid_index(self.position, self.button_size, dims, point)?;
+        if row + 1 == self.rows {
+            Some(0)
+        } else {
+            Some((row * self.columns + col + 1) as u8)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use piston::input::{Button, ButtonArgs, ButtonState, Event, Key};
+    use test_util::InputScript;
+
+    fn controller() -> GameboardController {
+        let mut cells = [[0; 9]; 9];
+        cells[0][0] = 5;
+        cells[0][1] = 3;
+        GameboardController::new(Gameboard::from_cells(cells))
+    }
+
+    fn key(state: ButtonState, key: Key) -> Event {
+        let args = ButtonArgs {
+            state,
+            button: Button::Keyboard(key),
+            scancode: None,
+        };
+        Event::Input(args.into(), None)
+    }
+
+    fn press(controller: &mut GameboardController, keys: &[Key]) {
+        for &k in keys {
+            controller.event([0.0; 2], 400.0, &key(ButtonState::Press, k));
+            controller.event([0.0; 2], 400.0, &key(ButtonState::Release, k));
         }
     }
+
+    #[test]
+    fn moves_with_wrap_around() {
+        let mut controller = controller();
+        press(&mut controller, &[Key::Right]);
+        assert_eq!(controller.selected_cell, Some([0, 0]));
+        press(&mut controller, &[Key::Left, Key::Up]);
+        assert_eq!(controller.selected_cell, Some([8, 8]));
+        press(&mut controller, &[Key::D, Key::S, Key::S]);
+        assert_eq!(controller.selected_cell, Some([0, 1]));
+    }
+
+    #[test]
+    fn enters_and_clears_values() {
+        let mut controller = controller();
+        press(&mut controller, &[Key::Tab]);
+        assert_eq!(controller.selected_cell, Some([2, 0]));
+        press(&mut controller, &[Key::NumPad4]);
+        assert_eq!(controller.gameboard.cells[0][2].value, 4);
+        press(
+            &mut controller,
+            &[Key::Tab, Key::D7, Key::Left, Key::Delete],
+        );
+        assert_eq!(controller.gameboard.cells[0][2].value, 0);
+        assert_eq!(controller.gameboard.cells[0][3].value, 7);
+        press(&mut controller, &[Key::Right, Key::D0]);
+        assert_eq!(controller.gameboard.cells[0][3].value, 0);
+
+        // Tab wraps around past the givens.
+        controller.selected_cell = Some([8, 8]);
+        press(&mut controller, &[Key::Tab]);
+        assert_eq!(controller.selected_cell, Some([2, 0]));
+    }
+
+    #[test]
+    fn uses_key_map() {
+        let mut controller = controller();
+        controller
+            .keymap
+            .parse("K up\nW none\nctrl+U undo")
+            .unwrap();
+        controller.selected_cell = Some([4, 4]);
+        press(&mut controller, &[Key::K, Key::W]);
+        assert_eq!(controller.selected_cell, Some([4, 3]));
+        press(&mut controller, &[Key::D9, Key::LCtrl, Key::U]);
+        assert_eq!(controller.gameboard.cells[3][4].value, 9);
+        controller.ctrl_held = true;
+        press(&mut controller, &[Key::U]);
+        assert_eq!(controller.gameboard.cells[3][4].value, 0);
+    }
+
+    #[test]
+    fn letters_enter_large_values() {
+        let geometry = "4x4".parse().unwrap();
+        let mut controller = controller();
+        controller.gameboard = Gameboard::with_geometry(geometry);
+        controller.selected_cell = Some([1, 1]);
+        press(&mut controller, &[Key::D]);
+        assert_eq!(controller.gameboard.cells[1][1].value, 13);
+        controller.ctrl_held = true;
+        press(&mut controller, &[Key::D]);
+        assert_eq!(controller.selected_cell, Some([2, 1]));
+    }
+
+    fn run(controller: &mut GameboardController, script: &str) {
+        let script: InputScript = script.parse().unwrap();
+        script.play(|e| controller.event([0.0; 2], 400.0, e));
+    }
+
+    #[test]
+    fn clicks_and_types() {
+        let mut controller = controller();
+        run(
+            &mut controller,
+            "move 120 80; click left; key D5\n\
+             # pencil marks\n\
+             key Right; press LShift; key D3; key D4; release LShift",
+        );
+        assert_eq!(controller.gameboard.cells[1][2].value, 5);
+        assert_eq!(controller.selected_cell, Some([3, 1]));
+        assert!(controller.gameboard.cells[1][3].has_note(3));
+        assert!(controller.gameboard.cells[1][3].has_note(4));
+
+        run(
+            &mut controller,
+            "key Left; key ctrl+Z; key ctrl+Z; key ctrl+Z",
+        );
+        assert_eq!(controller.gameboard.cells[1][2].value, 0);
+
+        // Clicks outside the board keep the selection.
+        run(&mut controller, "move 5 420; click left");
+        assert_eq!(controller.selected_cell, Some([2, 1]));
+    }
+
+    #[test]
+    fn tracks_focus() {
+        let mut controller = controller();
+        run(&mut controller, "unfocus");
+        assert!(controller.paused());
+        run(&mut controller, "focus");
+        assert!(!controller.paused());
+    }
+
+    #[test]
+    fn lays_out_number_pad() {
+        let pad = NumberPad::new([0.0; 2], 400.0, Geometry::CLASSIC);
+        assert_eq!(pad.position, [416.0, 272.0]);
+        assert_eq!(pad.button(5), [448.0, 304.0, 32.0, 32.0]);
+        assert_eq!(pad.button(0), [416.0, 368.0, 96.0, 32.0]);
+        assert_eq!(pad.value_at([417.0, 273.0]), Some(1));
+        assert_eq!(pad.value_at([511.0, 367.0]), Some(9));
+        assert_eq!(pad.value_at([500.0, 399.0]), Some(0));
+        assert_eq!(pad.value_at([512.0, 300.0]), None);
+        assert_eq!(pad.value_at([420.0, 400.0]), None);
+    }
+
+    #[test]
+    fn picks_digits_with_mouse() {
+        let mut controller = controller();
+        press(&mut controller, &[Key::Tab]);
+        // Click 5 on the number pad.
+        run(&mut controller, "move 464 320; click left");
+        assert_eq!(controller.gameboard.cells[0][2].value, 5);
+
+        // Drag 9 onto the middle cell, clear it and mark 1 instead.
+        run(
+            &mut controller,
+            "move 496 352; press left; move 200 200; release left",
+        );
+        assert_eq!(controller.selected_cell, Some([4, 4]));
+        assert_eq!(controller.gameboard.cells[4][4].value, 9);
+        run(&mut controller, "move 464 384; click left");
+        assert_eq!(controller.gameboard.cells[4][4].value, 0);
+        run(&mut controller, "move 432 288; click right");
+        assert!(controller.gameboard.cells[4][4].has_note(1));
+
+        // Letting go away from the board and the button places nothing.
+        run(&mut controller, "move 432 288; press left; move 600 10");
+        run(&mut controller, "release left");
+        assert_eq!(controller.gameboard.cells[4][4].value, 0);
+    }
+
+    #[test]
+    fn scrolls_through_values() {
+        let mut controller = controller();
+        controller.selected_cell = Some([2, 0]);
+        run(&mut controller, "scroll 0 1; scroll 0 1; scroll 0 1");
+        assert_eq!(controller.gameboard.cells[0][2].value, 3);
+        // Values passed on the way are not mistakes.
+        assert_eq!(controller.mistakes, 0);
+        run(&mut controller, "scroll 0 -1; scroll 0 -1; scroll 0 -1");
+        assert_eq!(controller.gameboard.cells[0][2].value, 0);
+        run(&mut controller, "scroll 0 -1");
+        assert_eq!(controller.gameboard.cells[0][2].value, 9);
+
+        // Givens stay put.
+        controller.selected_cell = Some([0, 0]);
+        run(&mut controller, "scroll 0 1");
+        assert_eq!(controller.gameboard.cells[0][0].value, 5);
+    }
+
+    fn puzzle() -> GameboardController {
+        let gameboard = Gameboard::load_sdm("static/puzzle.sdm").unwrap();
+        GameboardController::new(gameboard)
+    }
+
+    #[test]
+    fn counts_mistakes_by_assist_level() {
+        let mut controller = puzzle();
+        controller.selected_cell = Some([0, 0]);
+        // 5 is wrong, but clashes with no other cell.
+        press(&mut controller, &[Key::D5]);
+        assert_eq!(controller.mistakes, 0);
+        press(&mut controller, &[Key::C]);
+        assert_eq!(controller.assist, Assist::Solution);
+        press(&mut controller, &[Key::D5, Key::D3]);
+        assert_eq!(controller.mistakes, 1);
+        press(&mut controller, &[Key::D1]);
+        assert_eq!(controller.mistakes, 2);
+    }
+
+    #[test]
+    fn counts_scrolled_mistakes() {
+        let mut controller = puzzle();
+        controller.assist = Assist::Solution;
+        controller.mistake_limit = Some(2);
+        // The top left cell should be 3, so scrolling past 1 and 2 to it
+        // is fine.
+        run(
+            &mut controller,
+            "move 20 20; click left; scroll 0 1; scroll 0 1; scroll 0 1",
+        );
+        run(&mut controller, "key Right");
+        assert_eq!(controller.gameboard.cells[0][0].value, 3);
+        assert_eq!(controller.mistakes, 0);
+
+        // A wrong value left in the cell counts once the player moves on.
+        run(&mut controller, "key Left; scroll 0 1");
+        assert_eq!(controller.mistakes, 0);
+        run(&mut controller, "click left");
+        assert_eq!(controller.mistakes, 1);
+        run(&mut controller, "scroll 0 1; key D1");
+        assert_eq!(controller.mistakes, 2);
+        assert!(controller.lost());
+    }
+
+    #[test]
+    fn mistake_limit_ends_game() {
+        let mut controller = puzzle();
+        controller.mistake_limit = Some(2);
+        controller.selected_cell = Some([0, 0]);
+        press(&mut controller, &[Key::D1]);
+        assert!(!controller.game_over());
+        press(&mut controller, &[Key::D6]);
+        assert!(controller.lost());
+        assert!(controller.game_over());
+        let elapsed = controller.elapsed();
+
+        // The board stays as it was when the game was lost.
+        press(&mut controller, &[Key::D3, Key::H]);
+        run(&mut controller, "scroll 0 1; key ctrl+Z");
+        assert_eq!(controller.gameboard.cells[0][0].value, 6);
+        assert_eq!(controller.hint, None);
+        assert_eq!(controller.elapsed(), elapsed);
+
+        let resumed = GameboardController::resume(controller.saved_game());
+        assert_eq!(resumed.mistake_limit, Some(2));
+        assert!(resumed.lost());
+    }
 }
@@ -2,43 +2,97 @@
 
 use graphics::types::Color;
 use graphics::{CharacterCache, Context, Graphics};
+use std::time::Duration;
 
-use crate::gameboard_controller::GameboardController;
+use crate::gameboard::value_char;
+use crate::gameboard_controller::{GameboardController, NumberPad};
+use crate::generator::Difficulty;
+use crate::rules::Mark;
+use crate::stats::format_duration;
 
 /// Stores gameboard view settings.
+#[derive(Clone, Debug, PartialEq)]
 pub struct GameboardViewSettings {
     /// Position from left-top corner.
     pub position: [f64; 2],
     /// Size of gameboard along horizontal and vertical edge.
     pub size: f64,
+    /// Smallest gap kept between the board and the edges of the window.
+    pub margin: f64,
+    /// Pixels drawn per unit of window size, above 1 on HiDPI screens.
+    pub pixel_ratio: f64,
+    /// Color of the window around the board.
+    pub window_background_color: Color,
     /// Background color.
     pub background_color: Color,
     /// Border color.
     pub border_color: Color,
     /// Edge color around the whole board.
     pub board_edge_color: Color,
-    /// Edge color between the 3x3 sections.
+    /// Edge color between the boxes.
     pub section_edge_color: Color,
     /// Edge color between cells.
     pub cell_edge_color: Color,
     /// Edge radius around the whole board.
     pub board_edge_radius: f64,
-    /// Edge radius between the 3x3 sections.
+    /// Edge radius between the boxes.
     pub section_edge_radius: f64,
     /// Edge radius between cells.
     pub cell_edge_radius: f64,
     /// Selected cell background color.
     pub selected_cell_background_color: Color,
+    /// Selected cell background color while entering pencil marks.
+    pub notes_selected_cell_background_color: Color,
     /// Loaded cell background color.
     pub loaded_cell_background_color: Color,
     /// Invalid cell background color.
     pub invalid_cell_background_color: Color,
     /// Invalid selected cell background color.
     pub invalid_selected_cell_background_color: Color,
+    /// Color laid over the row, column and box of the selected cell, and
+    /// any other cells a variant rule ties to it.
+    pub peer_cell_highlight_color: Color,
+    /// Color laid over other cells with the value of the selected cell.
+    pub same_value_cell_highlight_color: Color,
     /// Completed game background color
     pub completed_background_color: Color,
+    /// Hint cell background color.
+    pub hint_cell_background_color: Color,
     /// Text color.
     pub text_color: Color,
+    /// Pencil mark text color.
+    pub notes_text_color: Color,
+    /// Largest pencil mark font size.
+    pub notes_font_size: u32,
+    /// Color of lines such as diagonals.
+    pub rule_line_color: Color,
+    /// Radius of lines such as diagonals.
+    pub rule_line_radius: f64,
+    /// Thermometer color.
+    pub thermometer_color: Color,
+    /// Killer cage edge color.
+    pub cage_edge_color: Color,
+    /// Killer cage edge radius.
+    pub cage_edge_radius: f64,
+    /// Killer cage sum font size.
+    pub cage_font_size: u32,
+    /// Color of the names of rules shown beside the board.
+    pub rule_text_color: Color,
+    /// Hint explanation text color.
+    pub hint_text_color: Color,
+    /// Hint explanation font size.
+    pub hint_font_size: u32,
+    /// Color of the clock, the counts beside the board and the statistics.
+    pub info_text_color: Color,
+    /// Font size of the clock, the counts beside the board and the
+    /// statistics.
+    pub info_font_size: u32,
+    /// Background color of the number pad buttons.
+    pub number_pad_background_color: Color,
+    /// Edge color of the number pad buttons.
+    pub number_pad_edge_color: Color,
+    /// Text color of the number pad buttons.
+    pub number_pad_text_color: Color,
 }
 
 impl GameboardViewSettings {
@@ -47,6 +101,9 @@ impl GameboardViewSettings {
         GameboardViewSettings {
             position: [10.0; 2],
             size: 400.0,
+            margin: 10.0,
+            pixel_ratio: 1.0,
+            window_background_color: [1.0; 4],
             background_color: [0.8, 0.8, 1.0, 1.0],
             border_color: [0.0, 0.0, 0.2, 1.0],
             board_edge_color: [0.0, 0.0, 0.2, 1.0],
@@ -56,13 +113,68 @@ impl GameboardViewSettings {
             section_edge_radius: 2.0,
             cell_edge_radius: 1.0,
             selected_cell_background_color: [0.9, 0.9, 1.0, 1.0],
+            notes_selected_cell_background_color: [0.9, 1.0, 0.9, 1.0],
             loaded_cell_background_color: [1.0, 1.0, 1.0, 1.0],
             invalid_cell_background_color: [1.0, 0.0, 0.0, 1.0],
             invalid_selected_cell_background_color: [1.0, 0.0, 0.5, 1.0],
+            peer_cell_highlight_color: [0.4, 0.4, 0.8, 0.15],
+            same_value_cell_highlight_color: [0.2, 0.2, 0.9, 0.35],
             completed_background_color: [0.0, 1.0, 0.0, 1.0],
+            hint_cell_background_color: [1.0, 1.0, 0.6, 1.0],
             text_color: [0.0, 0.0, 0.1, 1.0],
+            notes_text_color: [0.3, 0.3, 0.4, 1.0],
+            notes_font_size: 12,
+            rule_line_color: [0.5, 0.5, 0.8, 1.0],
+            rule_line_radius: 1.0,
+            thermometer_color: [0.7, 0.7, 0.75, 1.0],
+            cage_edge_color: [0.2, 0.2, 0.4, 1.0],
+            cage_edge_radius: 0.5,
+            cage_font_size: 10,
+            rule_text_color: [0.0, 0.0, 0.1, 1.0],
+            hint_text_color: [0.0, 0.0, 0.1, 1.0],
+            hint_font_size: 14,
+            info_text_color: [0.0, 0.0, 0.1, 1.0],
+            info_font_size: 14,
+            number_pad_background_color: [0.9, 0.9, 1.0, 1.0],
+            number_pad_edge_color: [0.0, 0.0, 0.2, 1.0],
+            number_pad_text_color: [0.0, 0.0, 0.1, 1.0],
         }
     }
+
+    /// Scales the board to the largest size fitting a window of
+    /// `window_size`, drawn with `draw_size` pixels, next to the number pad
+    /// and the text beside it, and centers them in the space left above
+    /// the hint explanation.
+    pub fn fit(&mut self, window_size: [f64; 2], draw_size: [u32; 2]) {
+        let footer = 3.0 * self.hint_font_size as f64 * 1.3;
+        let text = self.info_column_width();
+        let [width, height] = window_size;
+        let room = [width - 2.0 * self.margin, height - 2.0 * self.margin];
+        self.size = (room[0] / (1.0 + NumberPad::room(1.0)))
+            .min(room[0] - text)
+            .min(room[1] - footer)
+            .max(0.0);
+        let side = NumberPad::room(self.size).max(text);
+        self.position = [
+            (width - self.size - side) / 2.0,
+            (height - footer - self.size) / 2.0,
+        ];
+        if width > 0.0 {
+            self.pixel_ratio = draw_size[0] as f64 / width;
+        }
+    }
+    /// Width of the clock, the counts and the rule names beside the board,
+    /// counting the gap before them, which leaves room for about 16
+    /// characters of the info font.
+    fn info_column_width(&self) -> f64 {
+        self.info_font_size as f64 * (1.3 + 7.0)
+    }
+}
+
+impl Default for GameboardViewSettings {
+    fn default() -> GameboardViewSettings {
+        GameboardViewSettings::new()
+    }
 }
 
 /// Stores visual information about a gameboard.
@@ -87,9 +199,18 @@ impl GameboardView {
     ) where
         C: CharacterCache<Texture = G::Texture>,
     {
-        use graphics::{Image, Line, Rectangle, Transformed};
+        use graphics::{Ellipse, Line, Rectangle};
 
-        let ref settings = self.settings;
+        let settings = &self.settings;
+        if controller.show_stats {
+            self.draw_stats(controller, glyphs, c, g);
+            return;
+        }
+        let geometry = controller.gameboard.geometry;
+        let n = geometry.size();
+        let rules = &controller.gameboard.rules;
+        let marks: Vec<Mark> =
+            rules.iter().flat_map(|rule| rule.marks(geometry)).collect();
         let board_rect = [
             settings.position[0],
             settings.position[1],
@@ -112,20 +233,26 @@ impl GameboardView {
                 c.transform,
                 g,
             );
-            // Draw loaded and invalid cell backgrounds
-            for i in 0..9 {
-                for j in 0..9 {
+            // Draw loaded cell backgrounds, and those of cells flagged as
+            // wrong at the assist level in use.
+            for i in 0..n {
+                for j in 0..n {
                     if controller.gameboard.cells[i][j].loaded {
                         color_cell(
                             settings,
+                            n,
                             [j, i],
                             settings.loaded_cell_background_color,
                             c,
                             g,
                         );
-                    } else if controller.gameboard.cells[i][j].invalid {
+                    } else if controller
+                        .gameboard
+                        .flagged([j, i], controller.assist)
+                    {
                         color_cell(
                             settings,
+                            n,
                             [j, i],
                             settings.invalid_cell_background_color,
                             c,
@@ -135,49 +262,233 @@ impl GameboardView {
                 }
             }
 
+            // Shade the peers of the selected cell and the other cells with
+            // its value.
+            if let Some(ind) = controller.selected_cell {
+                if controller.highlight {
+                    let gameboard = &controller.gameboard;
+                    for peer in gameboard.peers(ind) {
+                        color_cell(
+                            settings,
+                            n,
+                            peer,
+                            settings.peer_cell_highlight_color,
+                            c,
+                            g,
+                        );
+                    }
+                    let val = gameboard.cells[ind[1]][ind[0]].value;
+                    for i in 0..n {
+                        for j in 0..n {
+                            if val != 0
+                                && gameboard.cells[i][j].value == val
+                                && [j, i] != ind
+                            {
+                                color_cell(
+                                    settings,
+                                    n,
+                                    [j, i],
+                                    settings.same_value_cell_highlight_color,
+                                    c,
+                                    g,
+                                );
+                            }
+                        }
+                    }
+                }
+            }
+
             // Draw selected cell background.
             if let Some(ind) = controller.selected_cell {
                 let cell = controller.gameboard.cells[ind[1]][ind[0]];
                 let color = if !cell.loaded {
-                    if !cell.invalid {
-                        settings.selected_cell_background_color
-                    } else {
+                    if controller.gameboard.flagged(ind, controller.assist) {
                         settings.invalid_selected_cell_background_color
+                    } else if controller.taking_notes() {
+                        settings.notes_selected_cell_background_color
+                    } else {
+                        settings.selected_cell_background_color
                     }
                 } else {
                     settings.loaded_cell_background_color
                 };
-                color_cell(settings, ind, color, c, g);
+                color_cell(settings, n, ind, color, c, g);
             };
+
+            // Draw hint cell background.
+            if let Some(ref hint) = controller.hint {
+                color_cell(
+                    settings,
+                    n,
+                    hint.cell,
+                    settings.hint_cell_background_color,
+                    c,
+                    g,
+                );
+            }
         }
 
-        // Draw characters.
-        let text_image = Image::new_color(settings.text_color);
-        let cell_size = settings.size / 9.0;
-        for j in 0..9 {
-            for i in 0..9 {
-                if let Some(ch) = controller.gameboard.char([i, j]) {
-                    let pos = [
-                        settings.position[0] + i as f64 * cell_size + 15.0,
-                        settings.position[1] + j as f64 * cell_size + 34.0,
-                    ];
-                    if let Ok(character) = glyphs.character(34, ch) {
-                        let ch_x = pos[0] + character.left();
-                        let ch_y = pos[1] - character.top();
-                        let text_image = text_image.src_rect([
-                            character.atlas_offset[0],
-                            character.atlas_offset[1],
-                            character.atlas_size[0],
-                            character.atlas_size[1],
-                        ]);
-                        text_image.draw(
-                            character.texture,
+        let cell_size = settings.size / n as f64;
+        let center = |[i, j]: [usize; 2]| {
+            [
+                settings.position[0] + (i as f64 + 0.5) * cell_size,
+                settings.position[1] + (j as f64 + 0.5) * cell_size,
+            ]
+        };
+
+        // Draw lines, thermometers and cages under the characters.
+        let rule_line = Line::new_round(
+            settings.rule_line_color,
+            settings.rule_line_radius,
+        );
+        let thermometer =
+            Line::new_round(settings.thermometer_color, cell_size * 0.15);
+        let cage_edge =
+            Line::new(settings.cage_edge_color, settings.cage_edge_radius);
+        for mark in &marks {
+            match mark {
+                Mark::Line(cells) | Mark::Thermometer(cells) => {
+                    let line = match mark {
+                        Mark::Line(_) => &rule_line,
+                        _ => &thermometer,
+                    };
+                    for pair in cells.windows(2) {
+                        let [x1, y1] = center(pair[0]);
+                        let [x2, y2] = center(pair[1]);
+                        line.draw(
+                            [x1, y1, x2, y2],
                             &c.draw_state,
-                            c.transform.trans(ch_x, ch_y),
+                            c.transform,
+                            g,
+                        );
+                    }
+                    if let (Mark::Thermometer(_), Some(&bulb)) =
+                        (mark, cells.first())
+                    {
+                        let [x, y] = center(bulb);
+                        let r = cell_size * 0.3;
+                        Ellipse::new(settings.thermometer_color).draw(
+                            [x - r, y - r, 2.0 * r, 2.0 * r],
+                            &c.draw_state,
+                            c.transform,
+                            g,
+                        );
+                    }
+                }
+                Mark::Cage { cells, sum } => {
+                    // Outline the cage just inside its cells, joining up
+                    // the edges of neighbouring cells.
+                    let inset = cell_size * 0.08;
+                    let inside = |i: isize, j: isize| {
+                        i >= 0
+                            && j >= 0
+                            && cells.contains(&[i as usize, j as usize])
+                    };
+                    for &[i, j] in cells {
+                        let (x, y) = (
+                            settings.position[0] + i as f64 * cell_size,
+                            settings.position[1] + j as f64 * cell_size,
+                        );
+                        let (i, j) = (i as isize, j as isize);
+                        let pad =
+                            |joined: bool| if joined { 0.0 } else { inset };
+                        let left = x + pad(inside(i - 1, j));
+                        let right = x + cell_size - pad(inside(i + 1, j));
+                        let top = y + pad(inside(i, j - 1));
+                        let bottom = y + cell_size - pad(inside(i, j + 1));
+                        let (x1, y1) = (x + inset, y + inset);
+                        let (x2, y2) =
+                            (x + cell_size - inset, y + cell_size - inset);
+                        let mut edges = Vec::new();
+                        if !inside(i, j - 1) {
+                            edges.push([left, y1, right, y1]);
+                        }
+                        if !inside(i, j + 1) {
+                            edges.push([left, y2, right, y2]);
+                        }
+                        if !inside(i - 1, j) {
+                            edges.push([x1, top, x1, bottom]);
+                        }
+                        if !inside(i + 1, j) {
+                            edges.push([x2, top, x2, bottom]);
+                        }
+                        for edge in edges {
+                            cage_edge.draw(edge, &c.draw_state, c.transform, g);
+                        }
+                    }
+                    // Write the sum in the top left cell of the cage.
+                    if let Some(&[i, j]) =
+                        cells.iter().min_by_key(|&&[i, j]| (j, i))
+                    {
+                        let x = settings.position[0] + i as f64 * cell_size;
+                        let y = settings.position[1] + j as f64 * cell_size;
+                        let size = settings
+                            .cage_font_size
+                            .min((cell_size * 0.3) as u32);
+                        draw_text(
+                            &sum.to_string(),
+                            [x + inset * 1.5, y + inset + size as f64],
+                            size,
+                            settings.cage_edge_color,
+                            settings.pixel_ratio,
+                            glyphs,
+                            c,
                             g,
                         );
                     }
                 }
+                Mark::Regions(_) | Mark::Label(_) => {}
+            }
+        }
+
+        // Draw characters centered in their cells, scaled to the cell size.
+        let font_size = (cell_size * 0.7) as u32;
+        let (bw, bh) = (geometry.box_width, geometry.box_height);
+        let mark_size = [cell_size / bw as f64, cell_size / bh as f64];
+        let notes_font_size = settings
+            .notes_font_size
+            .min((mark_size[0].min(mark_size[1]) * 0.8) as u32);
+        for j in 0..n {
+            for i in 0..n {
+                let x = settings.position[0] + i as f64 * cell_size;
+                let y = settings.position[1] + j as f64 * cell_size;
+                if let Some(ch) = controller.gameboard.char([i, j]) {
+                    draw_character(
+                        ch,
+                        [x + cell_size * 0.5, y + cell_size * 0.5],
+                        font_size,
+                        settings.text_color,
+                        settings.pixel_ratio,
+                        glyphs,
+                        c,
+                        g,
+                    );
+                    continue;
+                }
+                // Draw pencil marks in a grid shaped like a box.
+                let cell = controller.gameboard.cells[j][i];
+                for val in 1..=n as u8 {
+                    let ch = match value_char(val) {
+                        Some(ch) if cell.has_note(val) => ch,
+                        _ => continue,
+                    };
+                    let k = val as usize - 1;
+                    let (col, row) = (k % bw, k / bw);
+                    let pos = [
+                        x + (col as f64 + 0.5) * mark_size[0],
+                        y + (row as f64 + 0.5) * mark_size[1],
+                    ];
+                    draw_character(
+                        ch,
+                        pos,
+                        notes_font_size,
+                        settings.notes_text_color,
+                        settings.pixel_ratio,
+                        glyphs,
+                        c,
+                        g,
+                    );
+                }
             }
         }
 
@@ -190,24 +501,60 @@ impl GameboardView {
         );
 
         // Generate and draw the lines for the Sudoku Grid.
-        for i in 0..9 {
-            let x = settings.position[0] + i as f64 / 9.0 * settings.size;
-            let y = settings.position[1] + i as f64 / 9.0 * settings.size;
+        for i in 0..n {
+            let x = settings.position[0] + i as f64 * cell_size;
+            let y = settings.position[1] + i as f64 * cell_size;
             let x2 = settings.position[0] + settings.size;
             let y2 = settings.position[1] + settings.size;
 
             let vline = [x, settings.position[1], x, y2];
             let hline = [settings.position[0], y, x2, y];
 
-            // Draw Section Lines instead of Cell Lines
-            if (i % 3) == 0 {
-                section_edge.draw(vline, &c.draw_state, c.transform, g);
-                section_edge.draw(hline, &c.draw_state, c.transform, g);
-            }
-            // Draw the regular cell Lines
-            else {
-                cell_edge.draw(vline, &c.draw_state, c.transform, g);
-                cell_edge.draw(hline, &c.draw_state, c.transform, g);
+            // Draw Section Lines instead of Cell Lines, where boxes meet
+            let boxes = !rules.replaces_boxes();
+            let vedge = if boxes && i % bw == 0 {
+                &section_edge
+            } else {
+                &cell_edge
+            };
+            let hedge = if boxes && i % bh == 0 {
+                &section_edge
+            } else {
+                &cell_edge
+            };
+            vedge.draw(vline, &c.draw_state, c.transform, g);
+            hedge.draw(hline, &c.draw_state, c.transform, g);
+        }
+
+        // Draw the edges between regions replacing the boxes.
+        for mark in &marks {
+            if let Mark::Regions(regions) = mark {
+                for (j, row) in regions.iter().enumerate() {
+                    for (i, &region) in row.iter().enumerate() {
+                        let x =
+                            settings.position[0] + (i + 1) as f64 * cell_size;
+                        let y =
+                            settings.position[1] + (j + 1) as f64 * cell_size;
+                        if i + 1 < n && row[i + 1] != region {
+                            let vline = [x, y - cell_size, x, y];
+                            section_edge.draw(
+                                vline,
+                                &c.draw_state,
+                                c.transform,
+                                g,
+                            );
+                        }
+                        if j + 1 < n && regions[j + 1][i] != region {
+                            let hline = [x - cell_size, y, x, y];
+                            section_edge.draw(
+                                hline,
+                                &c.draw_state,
+                                c.transform,
+                                g,
+                            );
+                        }
+                    }
+                }
             }
         }
 
@@ -217,12 +564,203 @@ impl GameboardView {
             settings.board_edge_radius,
         )
         .draw(board_rect, &c.draw_state, c.transform, g);
+
+        // Draw the number pad beside the board.
+        let pad = NumberPad::new(settings.position, settings.size, geometry);
+        let pad_font_size = (pad.button_size * 0.6) as u32;
+        let pad_edge = Rectangle::new_border(
+            settings.number_pad_edge_color,
+            settings.cell_edge_radius,
+        );
+        for val in 0..=n as u8 {
+            let rect = pad.button(val);
+            Rectangle::new(settings.number_pad_background_color).draw(
+                rect,
+                &c.draw_state,
+                c.transform,
+                g,
+            );
+            pad_edge.draw(rect, &c.draw_state, c.transform, g);
+            let center = [rect[0] + rect[2] / 2.0, rect[1] + rect[3] / 2.0];
+            match value_char(val) {
+                Some(ch) => draw_character(
+                    ch,
+                    center,
+                    pad_font_size,
+                    settings.number_pad_text_color,
+                    settings.pixel_ratio,
+                    glyphs,
+                    c,
+                    g,
+                ),
+                None => {
+                    let size = (pad.button_size * 0.4) as u32;
+                    let pixel_size =
+                        (size as f64 * settings.pixel_ratio) as u32;
+                    let width =
+                        glyphs.width(pixel_size, "clear").unwrap_or(0.0)
+                            / settings.pixel_ratio;
+                    draw_text(
+                        "clear",
+                        [
+                            center[0] - width / 2.0,
+                            center[1] + size as f64 * 0.35,
+                        ],
+                        size,
+                        settings.number_pad_text_color,
+                        settings.pixel_ratio,
+                        glyphs,
+                        c,
+                        g,
+                    );
+                }
+            }
+        }
+
+        // Show the clock and counts beside the board, followed by the names
+        // of the rules that have no place on the board.
+        let info_height = settings.info_font_size as f64 * 1.3;
+        let mistakes = match controller.mistake_limit {
+            Some(limit) => {
+                format!("mistakes {}/{}", controller.mistakes, limit)
+            }
+            None => format!("mistakes {}", controller.mistakes),
+        };
+        let mut info = vec![
+            format_duration(controller.elapsed()),
+            mistakes,
+            format!("hints {}", controller.hints_used),
+            format!("assist {}", controller.assist),
+        ];
+        if controller.paused() {
+            info[0].push_str(" paused");
+        }
+        if controller.lost() {
+            info.push("game over".into());
+        }
+        if let Some(game) = controller.finished_game() {
+            if controller.stats.best(game.difficulty) == Some(game.elapsed) {
+                info.push("new best time!".into());
+            }
+        }
+        let x = settings.position[0] + settings.size + info_height;
+        for (k, line) in info.iter().enumerate() {
+            let y = settings.position[1] + (k + 1) as f64 * info_height;
+            draw_text(
+                line,
+                [x, y],
+                settings.info_font_size,
+                settings.info_text_color,
+                settings.pixel_ratio,
+                glyphs,
+                c,
+                g,
+            );
+        }
+        let top = settings.position[1] + (info.len() + 1) as f64 * info_height;
+        let line_height = settings.hint_font_size as f64 * 1.3;
+        let labels = marks.iter().filter_map(|mark| match mark {
+            Mark::Label(label) => Some(label),
+            _ => None,
+        });
+        for (k, label) in labels.enumerate() {
+            let y = top + k as f64 * line_height;
+            draw_text(
+                label,
+                [x, y],
+                settings.hint_font_size,
+                settings.rule_text_color,
+                settings.pixel_ratio,
+                glyphs,
+                c,
+                g,
+            );
+        }
+
+        // Explain the hint below the board, keeping the steps closest to
+        // the placement when there is no room for all of them.
+        if let Some(ref hint) = controller.hint {
+            if controller.hint_explained {
+                let steps = &hint.steps[hint.steps.len().saturating_sub(3)..];
+                for (k, step) in steps.iter().enumerate() {
+                    let x = settings.position[0];
+                    let y = settings.position[1]
+                        + settings.size
+                        + (k + 1) as f64 * line_height;
+                    draw_text(
+                        &step.to_string(),
+                        [x, y],
+                        settings.hint_font_size,
+                        settings.hint_text_color,
+                        settings.pixel_ratio,
+                        glyphs,
+                        c,
+                        g,
+                    );
+                }
+            }
+        }
     }
 }
 
-/// color an individual cell in the grid
+impl GameboardView {
+    /// Draw the times of finished games for every difficulty in place of
+    /// the board.
+    fn draw_stats<G: Graphics, C>(
+        &self,
+        controller: &GameboardController,
+        glyphs: &mut C,
+        c: &Context,
+        g: &mut G,
+    ) where
+        C: CharacterCache<Texture = G::Texture>,
+    {
+        let settings = &self.settings;
+        let stats = &controller.stats;
+        let time = |duration: Option<Duration>| {
+            duration.map_or_else(|| "-".into(), format_duration)
+        };
+        let mut lines = vec!["statistics".to_string(), String::new()];
+        for &difficulty in Difficulty::ALL.iter() {
+            lines.push(format!(
+                "{}: {} played, best {}, average {}",
+                difficulty,
+                stats.games_of(difficulty).count(),
+                time(stats.best(difficulty)),
+                time(stats.average(difficulty)),
+            ));
+        }
+        if let Some(game) = controller.finished_game() {
+            lines.push(String::new());
+            lines.push(format!(
+                "this game: {} in {}, {} mistakes, {} hints",
+                game.difficulty,
+                format_duration(game.elapsed),
+                game.mistakes,
+                game.hints,
+            ));
+        }
+        let line_height = settings.info_font_size as f64 * 1.5;
+        for (k, line) in lines.iter().enumerate() {
+            let y = settings.position[1] + (k + 1) as f64 * line_height;
+            draw_text(
+                line,
+                [settings.position[0], y],
+                settings.info_font_size,
+                settings.info_text_color,
+                settings.pixel_ratio,
+                glyphs,
+                c,
+                g,
+            );
+        }
+    }
+}
+
+/// color an individual cell in a grid `n` cells wide
 fn color_cell<G: Graphics>(
     settings: &GameboardViewSettings,
+    n: usize,
     ind: [usize; 2],
     color: [f32; 4],
     c: &Context,
@@ -230,7 +768,7 @@ fn color_cell<G: Graphics>(
 ) {
     use graphics::Rectangle;
 
-    let cell_size = settings.size / 9.0;
+    let cell_size = settings.size / n as f64;
     let pos = [ind[0] as f64 * cell_size, ind[1] as f64 * cell_size];
     let cell_rect = [
         settings.position[0] + pos[0],
@@ -240,3 +778,283 @@ fn color_cell<G: Graphics>(
     ];
     Rectangle::new(color).draw(cell_rect, &c.draw_state, c.transform, g);
 }
+
+/// draw a single character centered on `center`, rendering the glyph at
+/// `pixel_ratio` times the font size so it stays sharp on HiDPI screens
+#[allow(clippy::too_many_arguments)]
+fn draw_character<G: Graphics, C>(
+    ch: char,
+    center: [f64; 2],
+    font_size: u32,
+    color: Color,
+    pixel_ratio: f64,
+    glyphs: &mut C,
+    c: &Context,
+    g: &mut G,
+) where
+    C: CharacterCache<Texture = G::Texture>,
+{
+    use graphics::{Image, Transformed};
+
+    let pixel_size = (font_size as f64 * pixel_ratio) as u32;
+    if let Ok(character) = glyphs.character(pixel_size, ch) {
+        let [w, h] = character.atlas_size;
+        let ch_x = center[0] - w / pixel_ratio / 2.0;
+        let ch_y = center[1] - h / pixel_ratio / 2.0;
+        let text_image = Image::new_color(color).src_rect([
+            character.atlas_offset[0],
+            character.atlas_offset[1],
+            w,
+            h,
+        ]);
+        text_image.draw(
+            character.texture,
+            &c.draw_state,
+            c.transform
+                .trans(ch_x, ch_y)
+                .scale(1.0 / pixel_ratio, 1.0 / pixel_ratio),
+            g,
+        );
+    }
+}
+
+/// draw `text` with its baseline starting at `pos`, rendering the glyphs at
+/// `pixel_ratio` times the font size
+#[allow(clippy::too_many_arguments)]
+fn draw_text<G: Graphics, C>(
+    text: &str,
+    pos: [f64; 2],
+    font_size: u32,
+    color: Color,
+    pixel_ratio: f64,
+    glyphs: &mut C,
+    c: &Context,
+    g: &mut G,
+) where
+    C: CharacterCache<Texture = G::Texture>,
+{
+    use graphics::{Text, Transformed};
+
+    let pixel_size = (font_size as f64 * pixel_ratio) as u32;
+    let _ = Text::new_color(color, pixel_size).draw(
+        text,
+        glyphs,
+        &c.draw_state,
+        c.transform
+            .trans(pos[0], pos[1])
+            .scale(1.0 / pixel_ratio, 1.0 / pixel_ratio),
+        g,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameboard::{Assist, Gameboard, Geometry};
+    use crate::rules::Rules;
+    use crate::theme::Theme;
+    use piston::input::{Event, Input};
+    use std::env;
+    use std::fs::create_dir_all;
+    use test_util::{
+        RasterGlyphs, RasterGraphics, RecordingGlyphs, RecordingGraphics,
+    };
+
+    /// A game in progress on the puzzle in `static/puzzle.sdm`, with the
+    /// clock stopped at 1:23.
+    fn controller() -> GameboardController {
+        let gameboard = Gameboard::load_sdm("static/puzzle.sdm").unwrap();
+        let mut controller = GameboardController::new(gameboard);
+        let focus_lost = Event::Input(Input::Focus(false), None);
+        controller.event([0.0; 2], 400.0, &focus_lost);
+        let mut saved = controller.saved_game();
+        saved.elapsed = std::time::Duration::from_secs(83);
+        saved.mistakes = 1;
+        let mut controller = GameboardController::resume(saved);
+        controller.event([0.0; 2], 400.0, &focus_lost);
+        controller
+    }
+
+    /// Draws `controller` in a 640 by 480 window, the default size, and
+    /// compares the picture with `static/golden/NAME.png`, which is only
+    /// written instead when `UPDATE_GOLDEN` is set.
+    fn assert_golden(
+        name: &str,
+        controller: &GameboardController,
+        settings: GameboardViewSettings,
+    ) {
+        let mut view = GameboardView::new(settings);
+        view.settings.fit([640.0, 480.0], [640, 480]);
+        let mut glyphs =
+            RasterGlyphs::load("assets/FiraSans-Regular.ttf").unwrap();
+        let mut g = RasterGraphics::new(640, 480);
+        let c = g.context();
+        g.clear_color(view.settings.window_background_color);
+        view.draw(controller, &mut glyphs, &c, &mut g);
+
+        let golden = format!("static/golden/{}.png", name);
+        if env::var_os("UPDATE_GOLDEN").is_some() {
+            create_dir_all("static/golden").unwrap();
+            g.save_png(&golden).unwrap();
+            return;
+        }
+        let expected = RasterGraphics::load_png(&golden).unwrap_or_else(|e| {
+            panic!(
+                "cannot read `{}`: {}, run with UPDATE_GOLDEN=1 to write it",
+                golden, e
+            )
+        });
+        let differs = g.diff(&expected, 2);
+        if differs != Some(0) {
+            let actual = env::temp_dir().join(format!("{}.png", name));
+            let actual = actual.to_str().unwrap();
+            g.save_png(actual).unwrap();
+            panic!(
+                "{} differs from `{}` in {:?} pixels, drawn to `{}`",
+                name, golden, differs, actual
+            );
+        }
+    }
+
+    #[test]
+    fn colors_cells() {
+        let mut controller = controller();
+        controller.gameboard.set([0, 0], 6);
+        let view = GameboardView::new(GameboardViewSettings::new());
+        let settings = &view.settings;
+        let mut g = RecordingGraphics::new(640.0, 480.0);
+        let c = g.context();
+        view.draw(&controller, &mut RecordingGlyphs::new(), &c, &mut g);
+
+        let cell = |[j, i]: [usize; 2]| {
+            let size = settings.size / 9.0;
+            let [x, y] = settings.position;
+            [x + j as f64 * size, y + i as f64 * size, size, size]
+        };
+        assert_eq!(
+            g.fills(cell([0, 0])),
+            vec![settings.invalid_cell_background_color]
+        );
+        assert_eq!(
+            g.fills(cell([2, 0])),
+            vec![settings.loaded_cell_background_color]
+        );
+        assert!(g.fills(cell([4, 0])).is_empty());
+        let pad =
+            NumberPad::new(settings.position, settings.size, Geometry::CLASSIC);
+        assert_eq!(
+            g.fills(pad.button(5)),
+            vec![settings.number_pad_background_color]
+        );
+        let text = g.text();
+        assert!(text.starts_with("6164"), "{}", text);
+        assert!(text.contains("1:23 paused"), "{}", text);
+        assert!(text.contains("mistakes 1"), "{}", text);
+        assert!(text.contains("clear123456789"), "{}", text);
+    }
+
+    #[test]
+    fn flags_wrong_values() {
+        let mut controller = controller();
+        controller.assist = Assist::Solution;
+        controller.mistake_limit = Some(3);
+        // 7 is wrong, but clashes with no other cell.
+        controller.gameboard.set([4, 0], 7);
+        let view = GameboardView::new(GameboardViewSettings::new());
+        let settings = &view.settings;
+        let mut g = RecordingGraphics::new(640.0, 480.0);
+        let c = g.context();
+        view.draw(&controller, &mut RecordingGlyphs::new(), &c, &mut g);
+
+        let size = settings.size / 9.0;
+        let [x, y] = settings.position;
+        assert_eq!(
+            g.fills([x + 4.0 * size, y, size, size]),
+            vec![settings.invalid_cell_background_color]
+        );
+        let text = g.text();
+        assert!(text.contains("mistakes 1/3"), "{}", text);
+        assert!(text.contains("assist solution"), "{}", text);
+        assert!(!text.contains("game over"), "{}", text);
+
+        controller.mistakes = 3;
+        let mut g = RecordingGraphics::new(640.0, 480.0);
+        view.draw(&controller, &mut RecordingGlyphs::new(), &c, &mut g);
+        assert!(g.text().contains("game over"));
+    }
+
+    #[test]
+    fn draws_game() {
+        let mut controller = controller();
+        controller.gameboard.set([0, 0], 5);
+        controller.gameboard.set([4, 0], 4);
+        controller.gameboard.toggle_note([4, 4], 1);
+        controller.gameboard.toggle_note([4, 4], 9);
+        controller.selected_cell = Some([0, 0]);
+        assert_golden("game", &controller, GameboardViewSettings::new());
+    }
+
+    #[test]
+    fn draws_rules_in_dark_theme() {
+        let mut controller = controller();
+        let geometry = controller.gameboard.geometry;
+        let rules = "diagonals\ncage 10 r9c8 r9c9\nanti-knight\n";
+        controller.gameboard.rules = Rules::parse(rules, geometry).unwrap();
+        let dark = Theme::find("dark").unwrap();
+        assert_golden("rules-dark", &controller, dark.settings);
+    }
+
+    #[test]
+    fn draws_stats() {
+        let mut controller = controller();
+        controller.show_stats = true;
+        assert_golden("stats", &controller, GameboardViewSettings::new());
+    }
+
+    #[test]
+    fn fits_window() {
+        let mut settings = GameboardViewSettings::new();
+        settings.fit([640.0, 480.0], [1280, 960]);
+        let [x, y] = settings.position;
+        let side = settings.info_column_width();
+        assert!((x + (settings.size + side) / 2.0 - 320.0).abs() < 1e-9);
+        assert!(y >= settings.margin);
+        assert!(y + settings.size + 3.0 * 14.0 * 1.3 <= 480.0);
+        assert_eq!(settings.pixel_ratio, 2.0);
+
+        // Narrow windows leave room for the pad and the text beside it.
+        settings.fit([300.0, 900.0], [300, 900]);
+        assert_eq!(settings.size, 280.0 - side);
+        assert_eq!(settings.position[0], 10.0);
+        assert_eq!(settings.pixel_ratio, 1.0);
+    }
+
+    #[test]
+    fn keeps_number_pad_in_window() {
+        let mut settings = GameboardViewSettings::new();
+        for &[width, height] in
+            &[[640.0, 480.0], [300.0, 900.0], [400.0, 400.0]]
+        {
+            settings.fit([width, height], [width as u32, height as u32]);
+            let right = settings.position[0] + settings.size;
+            assert!(right + settings.info_column_width() <= width);
+            for shape in &["3x3", "2x2", "3x2", "4x4"] {
+                let geometry = shape.parse().unwrap();
+                let pad =
+                    NumberPad::new(settings.position, settings.size, geometry);
+                for val in 0..=geometry.size() as u8 {
+                    let [x, y, w, h] = pad.button(val);
+                    assert!(x >= 0.0 && y >= 0.0, "{} in {}", val, shape);
+                    assert!(
+                        x + w <= width && y + h <= height,
+                        "{} in {} at {}x{}",
+                        val,
+                        shape,
+                        width,
+                        height
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,247 @@
+//! Puzzle generator.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::gameboard::{Gameboard, Geometry};
+use crate::grader::grade;
+use crate::solver::{solve, uniqueness, Uniqueness};
+
+/// How hard a puzzle is, judged by the techniques needed to solve it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Difficulty {
+    /// Solvable with singles alone.
+    Easy,
+    /// Needs pointing pairs or box/line reductions.
+    Medium,
+    /// Needs naked or hidden subsets, or an X-Wing.
+    Hard,
+    /// Needs a Swordfish or more than the grader knows.
+    Expert,
+}
+
+impl Difficulty {
+    /// All difficulties, from easiest to hardest.
+    pub const ALL: [Difficulty; 4] = [
+        Difficulty::Easy,
+        Difficulty::Medium,
+        Difficulty::Hard,
+        Difficulty::Expert,
+    ];
+
+    /// Number of givens the generator stops removing at, scaled from the
+    /// classic board to one of `cells` cells.
+    fn min_givens(self, cells: usize) -> usize {
+        let classic = match self {
+            Difficulty::Easy => 36,
+            Difficulty::Medium => 30,
+            Difficulty::Hard | Difficulty::Expert => 0,
+        };
+        classic * cells / 81
+    }
+}
+
+impl fmt::Display for Difficulty {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Difficulty::Easy => "easy",
+            Difficulty::Medium => "medium",
+            Difficulty::Hard => "hard",
+            Difficulty::Expert => "expert",
+        })
+    }
+}
+
+impl FromStr for Difficulty {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Difficulty, String> {
+        Difficulty::ALL
+            .iter()
+            .copied()
+            .find(|difficulty| difficulty.to_string() == s)
+            .ok_or_else(|| {
+                format!(
+                    "unknown difficulty `{}`, expected easy, medium, hard \
+                     or expert",
+                    s
+                )
+            })
+    }
+}
+
+/// Number of puzzles carved while looking for one of the requested
+/// difficulty, before settling for the hardest one found.
+const ATTEMPTS: usize = 200;
+
+/// Generates a puzzle with boxes shaped like `geometry` and a unique
+/// solution.
+///
+/// Returns the puzzle with the difficulty it was graded as. That is
+/// `difficulty` unless no such puzzle turned up within a bounded number of
+/// attempts, in which case the hardest puzzle found is returned. The same
+/// arguments always produce the same puzzle.
+pub fn generate(
+    geometry: Geometry,
+    difficulty: Difficulty,
+    seed: u64,
+) -> (Gameboard, Difficulty) {
+    let mut rng = Rng::new(seed);
+    let mut closest: Option<(Difficulty, Gameboard)> = None;
+    for _ in 0..ATTEMPTS {
+        let puzzle = carve(&mut rng, geometry, difficulty);
+        let graded = grade(&puzzle).difficulty();
+        if graded == difficulty {
+            return (puzzle, graded);
+        }
+        if closest.as_ref().is_none_or(|&(best, _)| graded > best) {
+            closest = Some((graded, puzzle));
+        }
+    }
+    let (graded, puzzle) = closest.expect("at least one attempt is made");
+    (puzzle, graded)
+}
+
+/// Removes givens from a random grid while the solution stays unique and
+/// the puzzle no harder than `difficulty`.
+fn carve(
+    rng: &mut Rng,
+    geometry: Geometry,
+    difficulty: Difficulty,
+) -> Gameboard {
+    let size = geometry.size();
+    let mut cells = random_grid(rng, geometry);
+
+    // Remove givens in symmetric pairs, visiting the pairs in random order.
+    let mut pairs: Vec<usize> = (0..=size * size / 2).collect();
+    rng.shuffle(&mut pairs);
+    let mut givens = size * size;
+    for k in pairs {
+        if givens <= difficulty.min_givens(size * size) {
+            break;
+        }
+        let (a, b) = (k, size * size - 1 - k);
+        let saved = (cells[a / size][a % size], cells[b / size][b % size]);
+        cells[a / size][a % size] = 0;
+        cells[b / size][b % size] = 0;
+        let puzzle = Gameboard::from_values(geometry, &cells);
+        if uniqueness(&puzzle) == Uniqueness::Unique
+            && grade(&puzzle).difficulty() <= difficulty
+        {
+            givens -= if a == b { 1 } else { 2 };
+        } else {
+            cells[a / size][a % size] = saved.0;
+            cells[b / size][b % size] = saved.1;
+        }
+    }
+    Gameboard::from_values(geometry, &cells)
+}
+
+/// Creates a random completed grid.
+fn random_grid(rng: &mut Rng, geometry: Geometry) -> Vec<Vec<u8>> {
+    // The boxes on the diagonal share no row or column, so they are filled
+    // with shuffled digits first. On small boards some fillings cannot be
+    // completed, and those are drawn again.
+    let size = geometry.size();
+    let boxes_per_row = geometry.box_height;
+    loop {
+        let mut cells = vec![vec![0; size]; size];
+        for band in 0..geometry.box_width.min(geometry.box_height) {
+            let mut digits: Vec<u8> = (1..=size as u8).collect();
+            rng.shuffle(&mut digits);
+            for (k, &digit) in digits.iter().enumerate() {
+                let [j, i] = geometry.box_cell(band * boxes_per_row + band, k);
+                cells[i][j] = digit;
+            }
+        }
+        if let Some(solved) = solve(&Gameboard::from_values(geometry, &cells)) {
+            let values =
+                solved.cells.iter().map(|row| row.iter().map(|c| c.value));
+            return values.map(|row| row.collect()).collect();
+        }
+    }
+}
+
+/// Small deterministic random number generator (SplitMix64), so puzzles can
+/// be reproduced from their seed on every platform.
+pub(crate) struct Rng(u64);
+
+impl Rng {
+    pub(crate) fn new(seed: u64) -> Rng {
+        Rng(seed)
+    }
+
+    pub(crate) fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a number in `0..n`.
+    pub(crate) fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            items.swap(i, self.below(i + 1));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CLASSIC: Geometry = Geometry::CLASSIC;
+
+    fn givens(gameboard: &Gameboard) -> usize {
+        gameboard
+            .cells
+            .iter()
+            .flatten()
+            .filter(|c| c.loaded)
+            .count()
+    }
+
+    #[test]
+    fn generates_requested_difficulty() {
+        for &difficulty in Difficulty::ALL.iter() {
+            let (puzzle, graded) = generate(CLASSIC, difficulty, 7);
+            assert_eq!(graded, difficulty);
+            assert_eq!(uniqueness(&puzzle), Uniqueness::Unique);
+            assert_eq!(grade(&puzzle).difficulty(), difficulty);
+            assert!(givens(&puzzle) >= difficulty.min_givens(81));
+        }
+    }
+
+    #[test]
+    fn generates_other_sizes() {
+        for &shape in &["2x2", "3x2", "2x3", "4x3"] {
+            let geometry = shape.parse().unwrap();
+            let (puzzle, _) = generate(geometry, Difficulty::Easy, 7);
+            assert_eq!(puzzle.geometry, geometry);
+            assert_eq!(uniqueness(&puzzle), Uniqueness::Unique);
+            assert_eq!(grade(&puzzle).difficulty(), Difficulty::Easy);
+        }
+    }
+
+    #[test]
+    fn same_seed_same_puzzle() {
+        let (a, _) = generate(CLASSIC, Difficulty::Medium, 42);
+        let (b, _) = generate(CLASSIC, Difficulty::Medium, 42);
+        let (c, _) = generate(CLASSIC, Difficulty::Medium, 43);
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+    }
+
+    #[test]
+    fn parses_difficulty() {
+        for &difficulty in Difficulty::ALL.iter() {
+            assert_eq!(difficulty.to_string().parse(), Ok(difficulty));
+        }
+        assert!("impossible".parse::<Difficulty>().is_err());
+    }
+}
@@ -0,0 +1,917 @@
+//! Logical solver grading puzzles by the techniques they need.
+
+use std::fmt;
+
+use crate::gameboard::{value_char, Gameboard, Geometry};
+use crate::generator::Difficulty;
+use crate::rules::Layout;
+use crate::solver::solve;
+
+/// A named solving technique, ordered from easiest to hardest.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Technique {
+    /// A cell has only one candidate left.
+    NakedSingle,
+    /// A digit fits in only one cell of a row, column or box.
+    HiddenSingle,
+    /// A digit's candidates in a box all lie on one row or column.
+    PointingPair,
+    /// A digit's candidates in a row or column all lie in one box.
+    BoxLineReduction,
+    /// Two cells of a unit share the same two candidates.
+    NakedPair,
+    /// Two digits fit in only the same two cells of a unit.
+    HiddenPair,
+    /// Three cells of a unit share three candidates between them.
+    NakedTriple,
+    /// Three digits fit in only the same three cells of a unit.
+    HiddenTriple,
+    /// A digit is confined to the same two columns in two rows, or the
+    /// other way round.
+    XWing,
+    /// A digit is confined to the same three columns in three rows, or the
+    /// other way round.
+    Swordfish,
+}
+
+impl Technique {
+    /// All techniques, from easiest to hardest.
+    pub const ALL: [Technique; 10] = [
+        Technique::NakedSingle,
+        Technique::HiddenSingle,
+        Technique::PointingPair,
+        Technique::BoxLineReduction,
+        Technique::NakedPair,
+        Technique::HiddenPair,
+        Technique::NakedTriple,
+        Technique::HiddenTriple,
+        Technique::XWing,
+        Technique::Swordfish,
+    ];
+
+    /// Human readable name of the technique.
+    pub fn name(self) -> &'static str {
+        match self {
+            Technique::NakedSingle => "Naked single",
+            Technique::HiddenSingle => "Hidden single",
+            Technique::PointingPair => "Pointing pair",
+            Technique::BoxLineReduction => "Box/line reduction",
+            Technique::NakedPair => "Naked pair",
+            Technique::HiddenPair => "Hidden pair",
+            Technique::NakedTriple => "Naked triple",
+            Technique::HiddenTriple => "Hidden triple",
+            Technique::XWing => "X-Wing",
+            Technique::Swordfish => "Swordfish",
+        }
+    }
+
+    /// Score added to a puzzle's grade for every use of the technique.
+    pub fn weight(self) -> u32 {
+        match self {
+            Technique::NakedSingle => 1,
+            Technique::HiddenSingle => 2,
+            Technique::PointingPair => 5,
+            Technique::BoxLineReduction => 5,
+            Technique::NakedPair => 8,
+            Technique::HiddenPair => 10,
+            Technique::NakedTriple => 14,
+            Technique::HiddenTriple => 16,
+            Technique::XWing => 20,
+            Technique::Swordfish => 30,
+        }
+    }
+
+    /// Difficulty of a puzzle that needs the technique.
+    pub fn difficulty(self) -> Difficulty {
+        match self {
+            Technique::NakedSingle | Technique::HiddenSingle => {
+                Difficulty::Easy
+            }
+            Technique::PointingPair | Technique::BoxLineReduction => {
+                Difficulty::Medium
+            }
+            Technique::NakedPair
+            | Technique::HiddenPair
+            | Technique::NakedTriple
+            | Technique::HiddenTriple
+            | Technique::XWing => Difficulty::Hard,
+            Technique::Swordfish => Difficulty::Expert,
+        }
+    }
+}
+
+impl fmt::Display for Technique {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A single deduction made by the logical solver.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Step {
+    /// Technique used for the deduction.
+    pub technique: Technique,
+    /// Cell filled in by the step, and its value.
+    pub placement: Option<([usize; 2], u8)>,
+    /// Candidates ruled out by the step, as cell and digit.
+    pub eliminations: Vec<([usize; 2], u8)>,
+    /// Explanation of the deduction for the player.
+    pub explanation: String,
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.technique, self.explanation)
+    }
+}
+
+/// Result of grading a puzzle.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Grade {
+    /// Deductions made, in order.
+    pub steps: Vec<Step>,
+    /// Whether the techniques were enough to solve the puzzle.
+    pub solved: bool,
+    /// Sum of the weights of all techniques used.
+    pub score: u32,
+}
+
+impl Grade {
+    /// The hardest technique the puzzle needed.
+    pub fn hardest(&self) -> Option<Technique> {
+        self.steps.iter().map(|step| step.technique).max()
+    }
+
+    /// Number of times `technique` was used.
+    pub fn uses(&self, technique: Technique) -> usize {
+        self.steps
+            .iter()
+            .filter(|step| step.technique == technique)
+            .count()
+    }
+
+    /// Difficulty implied by the hardest technique needed.
+    ///
+    /// Puzzles the techniques cannot finish are graded as expert.
+    pub fn difficulty(&self) -> Difficulty {
+        if !self.solved {
+            return Difficulty::Expert;
+        }
+        self.hardest()
+            .map_or(Difficulty::Easy, |technique| technique.difficulty())
+    }
+}
+
+/// Solves the puzzle given by the loaded cells of `gameboard` with named
+/// techniques only, always using the easiest one that makes progress.
+///
+/// The techniques work on boxes, so puzzles whose variant rules replace the
+/// boxes are never solved.
+pub fn grade(gameboard: &Gameboard) -> Grade {
+    let mut grid = Grid::new(gameboard);
+    let mut steps = Vec::new();
+    let mut solved =
+        !gameboard.rules.replaces_boxes() && grid.load_givens(gameboard);
+    while solved {
+        if grid.is_full() {
+            break;
+        }
+        match grid.next_step() {
+            Some(step) => {
+                grid.apply(&step);
+                steps.push(step);
+            }
+            None => solved = false,
+        }
+    }
+    let score = steps.iter().map(|step| step.technique.weight()).sum();
+    Grade {
+        steps,
+        solved,
+        score,
+    }
+}
+
+/// The next cell a player can deduce, and the steps leading to it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Hint {
+    /// Cell that can be filled in.
+    pub cell: [usize; 2],
+    /// Value of the cell.
+    pub value: u8,
+    /// Deductions leading to the value, ending with its placement.
+    pub steps: Vec<Step>,
+}
+
+/// Finds the next cell of `gameboard` that can be deduced logically.
+///
+/// Player entries are taken into account only where they agree with the
+/// solution, so a hint never builds on a mistake or contradicts the givens.
+/// Returns `None` if the puzzle has no solution or the techniques get stuck,
+/// as they do on boards without boxes.
+pub fn hint(gameboard: &Gameboard) -> Option<Hint> {
+    if gameboard.rules.replaces_boxes() {
+        return None;
+    }
+    let solved = solve(gameboard)?;
+    let mut grid = Grid::new(gameboard);
+    grid.load_givens(gameboard);
+    for (i, row) in gameboard.cells.iter().enumerate() {
+        for (j, cell) in row.iter().enumerate() {
+            let value = cell.value;
+            if !cell.loaded && value != 0 && value == solved.cells[i][j].value {
+                grid.place([j, i], value);
+            }
+        }
+    }
+    let mut steps = Vec::new();
+    while let Some(step) = grid.next_step() {
+        grid.apply(&step);
+        let placement = step.placement;
+        steps.push(step);
+        if let Some((cell, value)) = placement {
+            return Some(Hint { cell, value, steps });
+        }
+    }
+    None
+}
+
+/// A row, column or box of the board.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Unit {
+    Row(usize),
+    Col(usize),
+    Box(usize),
+}
+
+impl Unit {
+    /// All rows, columns and boxes of a board shaped like `geometry`.
+    fn all(geometry: Geometry) -> impl Iterator<Item = Unit> {
+        let size = geometry.size();
+        (0..size)
+            .map(Unit::Row)
+            .chain((0..size).map(Unit::Col))
+            .chain((0..size).map(Unit::Box))
+    }
+
+    /// The cells of the unit.
+    fn cells(self, geometry: Geometry) -> Vec<[usize; 2]> {
+        let cell = |k| match self {
+            Unit::Row(i) => [k, i],
+            Unit::Col(i) => [i, k],
+            Unit::Box(i) => geometry.box_cell(i, k),
+        };
+        (0..geometry.size()).map(cell).collect()
+    }
+}
+
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Unit::Row(i) => write!(f, "row {}", i + 1),
+            Unit::Col(i) => write!(f, "column {}", i + 1),
+            Unit::Box(i) => write!(f, "box {}", i + 1),
+        }
+    }
+}
+
+/// Names a cell the way players read it, e.g. `r1c9` for the top right.
+pub(crate) fn cell_name(ind: [usize; 2]) -> String {
+    format!("r{}c{}", ind[1] + 1, ind[0] + 1)
+}
+
+/// Writes a value the way it is shown on the board.
+fn value_name(val: u8) -> char {
+    value_char(val).unwrap_or('?')
+}
+
+/// Lists the digits in `mask`, e.g. `3/7`.
+fn digit_list(mask: u32) -> String {
+    digits(mask)
+        .map(|d| value_name(d).to_string())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Iterates over the digits in `mask`.
+fn digits(mask: u32) -> impl Iterator<Item = u8> {
+    (1..32u8).filter(move |&d| mask & (1 << d) != 0)
+}
+
+/// Lists the cells of `eliminations`, e.g. `r1c2, r1c3`.
+fn cell_list(eliminations: &[([usize; 2], u8)]) -> String {
+    let mut cells: Vec<_> = eliminations.iter().map(|&(ind, _)| ind).collect();
+    cells.dedup();
+    let names: Vec<_> = cells.into_iter().map(cell_name).collect();
+    names.join(", ")
+}
+
+/// Calls `f` with every `k` element combination of `items`, stopping at the
+/// first combination for which it returns `Some`.
+fn find_combination<T: Copy, R>(
+    items: &[T],
+    k: usize,
+    f: &mut impl FnMut(&[T]) -> Option<R>,
+) -> Option<R> {
+    fn recurse<T: Copy, R>(
+        items: &[T],
+        k: usize,
+        chosen: &mut Vec<T>,
+        f: &mut impl FnMut(&[T]) -> Option<R>,
+    ) -> Option<R> {
+        if chosen.len() == k {
+            return f(chosen);
+        }
+        for (i, &item) in items.iter().enumerate() {
+            chosen.push(item);
+            let found = recurse(&items[i + 1..], k, chosen, f);
+            chosen.pop();
+            if found.is_some() {
+                return found;
+            }
+        }
+        None
+    }
+    recurse(items, k, &mut Vec::with_capacity(k), f)
+}
+
+/// Board state with the candidates left for every empty cell.
+struct Grid {
+    geometry: Geometry,
+    layout: Layout,
+    values: Vec<Vec<u8>>,
+    candidates: Vec<Vec<u32>>,
+}
+
+impl Grid {
+    /// Creates an empty grid with the shape and rules of `gameboard`.
+    fn new(gameboard: &Gameboard) -> Grid {
+        let geometry = gameboard.geometry;
+        let size = geometry.size();
+        Grid {
+            geometry,
+            layout: gameboard.rules.layout(geometry),
+            values: vec![vec![0; size]; size],
+            candidates: vec![vec![geometry.all_values(); size]; size],
+        }
+    }
+
+    fn size(&self) -> usize {
+        self.geometry.size()
+    }
+
+    /// Iterates over all cells, row by row.
+    fn cells(&self) -> impl Iterator<Item = [usize; 2]> {
+        let size = self.size();
+        (0..size * size).map(move |k| [k % size, k / size])
+    }
+
+    /// Iterates over the values a cell can hold.
+    fn all_digits(&self) -> impl Iterator<Item = u8> {
+        1..=self.size() as u8
+    }
+
+    /// Places the givens of `gameboard`, returning `false` if they conflict.
+    fn load_givens(&mut self, gameboard: &Gameboard) -> bool {
+        for (i, row) in gameboard.cells.iter().enumerate() {
+            for (j, cell) in row.iter().enumerate() {
+                if !cell.loaded || cell.value == 0 {
+                    continue;
+                }
+                if self.candidates[i][j] & (1 << cell.value) == 0 {
+                    return false;
+                }
+                self.place([j, i], cell.value);
+            }
+        }
+        true
+    }
+
+    fn value(&self, ind: [usize; 2]) -> u8 {
+        self.values[ind[1]][ind[0]]
+    }
+
+    fn candidates(&self, ind: [usize; 2]) -> u32 {
+        self.candidates[ind[1]][ind[0]]
+    }
+
+    fn is_full(&self) -> bool {
+        self.values.iter().flatten().all(|&value| value != 0)
+    }
+
+    fn place(&mut self, ind: [usize; 2], val: u8) {
+        self.values[ind[1]][ind[0]] = val;
+        self.candidates[ind[1]][ind[0]] = 0;
+        let [b, a] = ind;
+        let layout = &self.layout;
+        let units = layout.cell_units[a][b].iter();
+        let cells = units.flat_map(|&n| &layout.units[n]);
+        for &[j, i] in cells.chain(&layout.peers[a][b]) {
+            self.candidates[i][j] &= !(1 << val);
+        }
+    }
+
+    fn apply(&mut self, step: &Step) {
+        if let Some((ind, val)) = step.placement {
+            self.place(ind, val);
+        }
+        for &([j, i], val) in &step.eliminations {
+            self.candidates[i][j] &= !(1 << val);
+        }
+    }
+
+    /// Finds a deduction using the easiest technique that makes progress.
+    fn next_step(&self) -> Option<Step> {
+        let stuck = self
+            .cells()
+            .any(|ind| self.value(ind) == 0 && self.candidates(ind) == 0);
+        if stuck {
+            return None;
+        }
+        Technique::ALL
+            .iter()
+            .find_map(|&technique| self.find(technique))
+    }
+
+    fn find(&self, technique: Technique) -> Option<Step> {
+        match technique {
+            Technique::NakedSingle => self.naked_single(),
+            Technique::HiddenSingle => self.hidden_single(),
+            Technique::PointingPair => self.pointing_pair(),
+            Technique::BoxLineReduction => self.box_line_reduction(),
+            Technique::NakedPair => self.naked_subset(technique, 2),
+            Technique::HiddenPair => self.hidden_subset(technique, 2),
+            Technique::NakedTriple => self.naked_subset(technique, 3),
+            Technique::HiddenTriple => self.hidden_subset(technique, 3),
+            Technique::XWing => self.fish(technique, 2),
+            Technique::Swordfish => self.fish(technique, 3),
+        }
+    }
+
+    /// Cells of `unit` that may still hold `d`.
+    fn positions(&self, unit: Unit, d: u8) -> Vec<[usize; 2]> {
+        let cells = unit.cells(self.geometry).into_iter();
+        let positions =
+            cells.filter(|&ind| self.candidates(ind) & (1 << d) != 0);
+        positions.collect()
+    }
+
+    /// Cells among `cells` that may still hold `d`, as eliminations.
+    fn eliminate<I>(&self, cells: I, d: u8) -> Vec<([usize; 2], u8)>
+    where
+        I: IntoIterator<Item = [usize; 2]>,
+    {
+        let cells = cells.into_iter();
+        cells
+            .filter(|&ind| self.candidates(ind) & (1 << d) != 0)
+            .map(|ind| (ind, d))
+            .collect()
+    }
+
+    fn naked_single(&self) -> Option<Step> {
+        self.cells().find_map(|ind| {
+            let candidates = self.candidates(ind);
+            if candidates.count_ones() != 1 {
+                return None;
+            }
+            let val = candidates.trailing_zeros() as u8;
+            Some(Step {
+                technique: Technique::NakedSingle,
+                placement: Some((ind, val)),
+                eliminations: Vec::new(),
+                explanation: format!(
+                    "{} can only be {}",
+                    cell_name(ind),
+                    value_name(val)
+                ),
+            })
+        })
+    }
+
+    fn hidden_single(&self) -> Option<Step> {
+        for unit in Unit::all(self.geometry) {
+            for d in self.all_digits() {
+                let positions = self.positions(unit, d);
+                if positions.len() != 1 {
+                    continue;
+                }
+                let ind = positions[0];
+                return Some(Step {
+                    technique: Technique::HiddenSingle,
+                    placement: Some((ind, d)),
+                    eliminations: Vec::new(),
+                    explanation: format!(
+                        "{} fits only at {} in {}",
+                        value_name(d),
+                        cell_name(ind),
+                        unit
+                    ),
+                });
+            }
+        }
+        None
+    }
+
+    fn pointing_pair(&self) -> Option<Step> {
+        let geometry = self.geometry;
+        for sec in 0..self.size() {
+            for d in self.all_digits() {
+                let positions = self.positions(Unit::Box(sec), d);
+                if positions.len() < 2 {
+                    continue;
+                }
+                let [x, y] = positions[0];
+                let line = if positions.iter().all(|ind| ind[1] == y) {
+                    Unit::Row(y)
+                } else if positions.iter().all(|ind| ind[0] == x) {
+                    Unit::Col(x)
+                } else {
+                    continue;
+                };
+                let outside = line.cells(geometry).into_iter();
+                let outside =
+                    outside.filter(|&ind| geometry.section(ind) != sec);
+                let eliminations = self.eliminate(outside, d);
+                if eliminations.is_empty() {
+                    continue;
+                }
+                let explanation = format!(
+                    "{} in box {} lies on {}, so it is removed from {}",
+                    value_name(d),
+                    sec + 1,
+                    line,
+                    cell_list(&eliminations)
+                );
+                return Some(Step {
+                    technique: Technique::PointingPair,
+                    placement: None,
+                    eliminations,
+                    explanation,
+                });
+            }
+        }
+        None
+    }
+
+    fn box_line_reduction(&self) -> Option<Step> {
+        let geometry = self.geometry;
+        let size = self.size();
+        let lines = (0..size).map(Unit::Row).chain((0..size).map(Unit::Col));
+        for line in lines {
+            let line_cells = line.cells(geometry);
+            for d in self.all_digits() {
+                let positions = self.positions(line, d);
+                if positions.len() < 2 {
+                    continue;
+                }
+                let sec = geometry.section(positions[0]);
+                if positions.iter().any(|&ind| geometry.section(ind) != sec) {
+                    continue;
+                }
+                let inside = Unit::Box(sec).cells(geometry).into_iter();
+                let inside = inside.filter(|ind| !line_cells.contains(ind));
+                let eliminations = self.eliminate(inside, d);
+                if eliminations.is_empty() {
+                    continue;
+                }
+                let explanation = format!(
+                    "{} in {} lies in box {}, so it is removed from {}",
+                    value_name(d),
+                    line,
+                    sec + 1,
+                    cell_list(&eliminations)
+                );
+                return Some(Step {
+                    technique: Technique::BoxLineReduction,
+                    placement: None,
+                    eliminations,
+                    explanation,
+                });
+            }
+        }
+        None
+    }
+
+    /// Finds `n` cells of a unit holding only `n` candidates between them.
+    fn naked_subset(&self, technique: Technique, n: usize) -> Option<Step> {
+        for unit in Unit::all(self.geometry) {
+            let cells = unit.cells(self.geometry);
+            let open: Vec<_> = cells
+                .iter()
+                .copied()
+                .filter(|&ind| {
+                    let count = self.candidates(ind).count_ones() as usize;
+                    count >= 2 && count <= n
+                })
+                .collect();
+            let step = find_combination(&open, n, &mut |subset| {
+                let mask = subset
+                    .iter()
+                    .fold(0, |mask, &ind| mask | self.candidates(ind));
+                if mask.count_ones() as usize != n {
+                    return None;
+                }
+                let others =
+                    cells.iter().copied().filter(|ind| !subset.contains(ind));
+                let others: Vec<_> = others.collect();
+                let eliminations: Vec<_> = digits(mask)
+                    .flat_map(|d| self.eliminate(others.iter().copied(), d))
+                    .collect();
+                if eliminations.is_empty() {
+                    return None;
+                }
+                let names: Vec<_> =
+                    subset.iter().copied().map(cell_name).collect();
+                let explanation = format!(
+                    "{} in {} hold only {}, so those digits are removed \
+                     from {}",
+                    names.join(", "),
+                    unit,
+                    digit_list(mask),
+                    cell_list(&eliminations)
+                );
+                Some(Step {
+                    technique,
+                    placement: None,
+                    eliminations,
+                    explanation,
+                })
+            });
+            if step.is_some() {
+                return step;
+            }
+        }
+        None
+    }
+
+    /// Finds `n` digits that fit in only the same `n` cells of a unit.
+    fn hidden_subset(&self, technique: Technique, n: usize) -> Option<Step> {
+        for unit in Unit::all(self.geometry) {
+            let open: Vec<u8> = self
+                .all_digits()
+                .filter(|&d| {
+                    let count = self.positions(unit, d).len();
+                    count >= 2 && count <= n
+                })
+                .collect();
+            let step = find_combination(&open, n, &mut |subset| {
+                let mut cells: Vec<[usize; 2]> = subset
+                    .iter()
+                    .flat_map(|&d| self.positions(unit, d))
+                    .collect();
+                cells.sort_by_key(|ind| (ind[1], ind[0]));
+                cells.dedup();
+                if cells.len() != n {
+                    return None;
+                }
+                let mask = subset.iter().fold(0, |mask, &d| mask | (1 << d));
+                let mut eliminations = Vec::new();
+                for &ind in &cells {
+                    for d in digits(self.candidates(ind) & !mask) {
+                        eliminations.push((ind, d));
+                    }
+                }
+                if eliminations.is_empty() {
+                    return None;
+                }
+                let names: Vec<_> =
+                    cells.iter().copied().map(cell_name).collect();
+                let explanation = format!(
+                    "{} fit only at {} in {}, so other candidates are \
+                     removed there",
+                    digit_list(mask),
+                    names.join(", "),
+                    unit
+                );
+                Some(Step {
+                    technique,
+                    placement: None,
+                    eliminations,
+                    explanation,
+                })
+            });
+            if step.is_some() {
+                return step;
+            }
+        }
+        None
+    }
+
+    /// Finds `n` rows in which a digit is confined to the same `n` columns,
+    /// or `n` columns in which it is confined to the same `n` rows.
+    fn fish(&self, technique: Technique, n: usize) -> Option<Step> {
+        type Line = fn(usize) -> Unit;
+        for &by_rows in &[true, false] {
+            // Base lines hold the digit, cover lines cross them.
+            let (base, cover): (Line, Line) = if by_rows {
+                (Unit::Row, Unit::Col)
+            } else {
+                (Unit::Col, Unit::Row)
+            };
+            let base_index = |ind: [usize; 2]| ind[by_rows as usize];
+            let cover_index = |ind: [usize; 2]| ind[!by_rows as usize];
+            for d in self.all_digits() {
+                let lines: Vec<(usize, u32)> = (0..self.size())
+                    .filter_map(|i| {
+                        let positions = self.positions(base(i), d);
+                        let count = positions.len();
+                        if count < 2 || count > n {
+                            return None;
+                        }
+                        let mask = positions
+                            .iter()
+                            .fold(0, |mask, &ind| mask | 1 << cover_index(ind));
+                        Some((i, mask))
+                    })
+                    .collect();
+                let step = find_combination(&lines, n, &mut |subset| {
+                    let mask = subset.iter().fold(0, |mask, &(_, m)| mask | m);
+                    if mask.count_ones() as usize != n {
+                        return None;
+                    }
+                    let bases: Vec<_> =
+                        subset.iter().map(|&(i, _)| i).collect();
+                    let covers: Vec<_> = (0..self.size())
+                        .filter(|&k| mask & (1 << k) != 0)
+                        .collect();
+                    let mut eliminations = Vec::new();
+                    for &k in &covers {
+                        let cells = cover(k).cells(self.geometry).into_iter();
+                        let others = cells
+                            .filter(|&ind| !bases.contains(&base_index(ind)));
+                        eliminations.extend(self.eliminate(others, d));
+                    }
+                    if eliminations.is_empty() {
+                        return None;
+                    }
+                    let names = |lines: &[usize], unit: fn(usize) -> Unit| {
+                        let names: Vec<_> = lines
+                            .iter()
+                            .map(|&i| unit(i).to_string())
+                            .collect();
+                        names.join(", ")
+                    };
+                    let explanation = format!(
+                        "{} in {} is confined to {}, so it is removed from {}",
+                        value_name(d),
+                        names(&bases, base),
+                        names(&covers, cover),
+                        cell_list(&eliminations)
+                    );
+                    Some(Step {
+                        technique,
+                        placement: None,
+                        eliminations,
+                        explanation,
+                    })
+                });
+                if step.is_some() {
+                    return step;
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::corpus::{board, puzzle, CORPUS};
+
+    /// Checks every step of `grade` against the real solution of `puzzle`.
+    fn assert_sound(puzzle: &Gameboard, grade: &Grade) {
+        let solved = solve(puzzle).unwrap();
+        for step in &grade.steps {
+            if let Some(([j, i], val)) = step.placement {
+                assert_eq!(solved.cells[i][j].value, val, "{}", step);
+            }
+            for &([j, i], val) in &step.eliminations {
+                assert_ne!(solved.cells[i][j].value, val, "{}", step);
+            }
+        }
+    }
+
+    #[test]
+    fn grades_puzzles() {
+        for &(puzzle, difficulty) in CORPUS {
+            let puzzle = board(puzzle);
+            let grade = grade(&puzzle);
+            assert_eq!(grade.difficulty(), difficulty);
+            assert_eq!(grade.solved, difficulty != Difficulty::Expert);
+            assert_sound(&puzzle, &grade);
+        }
+    }
+
+    #[test]
+    fn explains_steps() {
+        let puzzle = puzzle(Difficulty::Easy);
+        let step = &grade(&puzzle).steps[0];
+        assert_eq!(step.to_string(), "Naked single: r5c6 can only be 4");
+    }
+
+    #[test]
+    fn finds_x_wing() {
+        let puzzle = board(
+            "1.....569492.561.8.561.924...964.8.1.64.1....218.356.4.4.5...169.5.614.2621.....5",
+        );
+        let grade = grade(&puzzle);
+        assert_eq!(grade.hardest(), Some(Technique::XWing));
+        assert!(grade.solved);
+        assert_sound(&puzzle, &grade);
+    }
+
+    #[test]
+    fn finds_swordfish() {
+        let puzzle = board(
+            "200100080000049150000008009009030070403000805010090300700500000052910000040002006",
+        );
+        let grade = grade(&puzzle);
+        let step = grade
+            .steps
+            .iter()
+            .find(|step| step.technique == Technique::Swordfish)
+            .expect("puzzle needs a swordfish");
+        assert_eq!(
+            step.explanation,
+            "7 in row 3, row 5, row 9 is confined to column 2, column 4, \
+             column 5, so it is removed from r2c2, r1c5"
+        );
+        assert_sound(&puzzle, &grade);
+    }
+
+    #[test]
+    fn score_sums_weights() {
+        let puzzle = puzzle(Difficulty::Hard);
+        let grade = grade(&puzzle);
+        let score: u32 = Technique::ALL
+            .iter()
+            .map(|&t| t.weight() * grade.uses(t) as u32)
+            .sum();
+        assert_eq!(grade.score, score);
+        assert_eq!(
+            grade.steps.iter().filter(|s| s.placement.is_some()).count(),
+            81 - 26
+        );
+    }
+
+    #[test]
+    fn grades_other_sizes() {
+        let puzzle = Gameboard::from_cells([
+            [0, 0, 3, 0, 1, 0],
+            [5, 6, 0, 3, 2, 0],
+            [0, 5, 4, 2, 0, 3],
+            [2, 0, 6, 4, 5, 0],
+            [0, 1, 2, 0, 4, 5],
+            [0, 4, 0, 1, 0, 0],
+        ]);
+        let grade = grade(&puzzle);
+        assert!(grade.solved);
+        assert_sound(&puzzle, &grade);
+
+        // Values above 9 are named by their letters.
+        let mut puzzle =
+            solve(&Gameboard::with_geometry("4x4".parse().unwrap())).unwrap();
+        for cell in puzzle.cells.iter_mut().flatten() {
+            cell.loaded = true;
+        }
+        let j = puzzle.cells[0].iter().position(|c| c.value == 16).unwrap();
+        puzzle.cells[0][j].loaded = false;
+        let step = &super::grade(&puzzle).steps[0];
+        assert_eq!(step.explanation, format!("r1c{} can only be G", j + 1));
+    }
+
+    #[test]
+    fn hints_next_cell() {
+        let mut puzzle = puzzle(Difficulty::Hard);
+        let solved = solve(&puzzle).unwrap();
+        let hint = hint(&puzzle).unwrap();
+        let [j, i] = hint.cell;
+        assert_eq!(hint.value, solved.cells[i][j].value);
+        assert_eq!(
+            hint.steps.last().unwrap().placement,
+            Some((hint.cell, hint.value))
+        );
+
+        // Correct entries are built on, so the next hint is elsewhere.
+        puzzle.set(hint.cell, hint.value);
+        let next = super::hint(&puzzle).unwrap();
+        assert_ne!(next.cell, hint.cell);
+    }
+
+    #[test]
+    fn hints_ignore_mistakes() {
+        let mut puzzle = puzzle(Difficulty::Easy);
+        // r5c6 is a naked 4, but the player has written a 6 there.
+        puzzle.set([5, 4], 6);
+        let hint = hint(&puzzle).unwrap();
+        assert_eq!((hint.cell, hint.value), ([5, 4], 4));
+    }
+
+    #[test]
+    fn no_hint_for_solved_puzzle() {
+        let puzzle = puzzle(Difficulty::Easy);
+        let solved = solve(&puzzle).unwrap();
+        assert_eq!(hint(&solved), None);
+    }
+}
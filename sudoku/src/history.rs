@@ -0,0 +1,249 @@
+//! Undo and redo history of moves.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::gameboard::{Cell, Gameboard, MAX_SIZE};
+
+/// A change to a single cell, recorded with its state before and after.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Change {
+    /// Location of the cell.
+    pub ind: [usize; 2],
+    /// Cell before the move.
+    pub before: Cell,
+    /// Cell after the move.
+    pub after: Cell,
+}
+
+/// All cell changes made by one player action.
+///
+/// Placing a digit can also change the `invalid` flags and pencil marks of
+/// other cells, so a move usually touches more than one cell.
+pub type Move = Vec<Change>;
+
+/// Stores the moves that can be undone and redone.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct History {
+    undo: Vec<Move>,
+    redo: Vec<Move>,
+}
+
+impl History {
+    /// Creates an empty history.
+    pub fn new() -> History {
+        History::default()
+    }
+
+    /// Records the move that turned `before` into `after`.
+    ///
+    /// Recording a move that changed something discards the redo stack.
+    pub fn record(&mut self, before: &Gameboard, after: &Gameboard) {
+        let mut changes = Vec::new();
+        for i in 0..before.size() {
+            for j in 0..before.size() {
+                let (old, new) = (before.cells[i][j], after.cells[i][j]);
+                if old != new {
+                    changes.push(Change {
+                        ind: [j, i],
+                        before: old,
+                        after: new,
+                    });
+                }
+            }
+        }
+        if !changes.is_empty() {
+            self.undo.push(changes);
+            self.redo.clear();
+        }
+    }
+
+    /// Whether there is a move to undo.
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    /// Whether there is a move to redo.
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// Whether every recorded change lies on a board of `size`.
+    pub fn fits(&self, size: usize) -> bool {
+        let mut changes = self.undo.iter().chain(&self.redo).flatten();
+        changes.all(|change| change.ind.iter().all(|&k| k < size))
+    }
+
+    /// Reverts the last move, returning whether there was one.
+    pub fn undo(&mut self, gameboard: &mut Gameboard) -> bool {
+        match self.undo.pop() {
+            Some(changes) => {
+                apply(gameboard, &changes, |change| change.before);
+                self.redo.push(changes);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Repeats the last undone move, returning whether there was one.
+    pub fn redo(&mut self, gameboard: &mut Gameboard) -> bool {
+        match self.redo.pop() {
+            Some(changes) => {
+                apply(gameboard, &changes, |change| change.after);
+                self.undo.push(changes);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+fn apply(
+    gameboard: &mut Gameboard,
+    changes: &[Change],
+    cell: impl Fn(&Change) -> Cell,
+) {
+    for change in changes {
+        let [j, i] = change.ind;
+        gameboard.cells[i][j] = cell(change);
+    }
+    gameboard.update_conflicts();
+    gameboard.update_completed();
+}
+
+/// Writes one move per line, prefixed with `u` if it can be undone or `r` if
+/// it can be redone. Each change is written as `x,y:before>after`, a cell as
+/// `value,loaded,invalid,notes`.
+impl fmt::Display for History {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let stacks = [('u', &self.undo), ('r', &self.redo)];
+        for &(tag, stack) in &stacks {
+            for changes in stack.iter() {
+                write!(f, "{}", tag)?;
+                for change in changes {
+                    let [x, y] = change.ind;
+                    write!(
+                        f,
+                        " {},{}:{}>{}",
+                        x, y, change.before, change.after
+                    )?;
+                }
+                writeln!(f)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for History {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<History, String> {
+        let mut history = History::new();
+        for (n, line) in s.lines().enumerate() {
+            let error = || format!("invalid history entry on line {}", n + 1);
+            let mut words = line.split_whitespace();
+            let stack = match words.next() {
+                Some("u") => &mut history.undo,
+                Some("r") => &mut history.redo,
+                None => continue,
+                Some(_) => return Err(error()),
+            };
+            let changes = words
+                .map(parse_change)
+                .collect::<Option<Move>>()
+                .ok_or_else(error)?;
+            stack.push(changes);
+        }
+        Ok(history)
+    }
+}
+
+fn parse_change(s: &str) -> Option<Change> {
+    let (ind, cells) = s.split_once(':')?;
+    let (x, y) = ind.split_once(',')?;
+    let (before, after) = cells.split_once('>')?;
+    let ind = [x.parse().ok()?, y.parse().ok()?];
+    if ind[0] >= MAX_SIZE || ind[1] >= MAX_SIZE {
+        return None;
+    }
+    Some(Change {
+        ind,
+        before: before.parse().ok()?,
+        after: after.parse().ok()?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn play(
+        history: &mut History,
+        gameboard: &mut Gameboard,
+        f: impl FnOnce(&mut Gameboard),
+    ) {
+        let before = gameboard.clone();
+        f(gameboard);
+        history.record(&before, gameboard);
+    }
+
+    #[test]
+    fn undo_restores_flags_and_notes() {
+        let start = Gameboard::new();
+        let mut gameboard = start.clone();
+        let mut history = History::new();
+        play(&mut history, &mut gameboard, |g| g.toggle_note([1, 0], 4));
+        let after_note = gameboard.clone();
+        play(&mut history, &mut gameboard, |g| g.set([0, 0], 4));
+        play(&mut history, &mut gameboard, |g| g.set([8, 0], 4));
+        assert!(gameboard.cells[0][8].invalid);
+
+        assert!(history.undo(&mut gameboard));
+        assert!(!gameboard.cells[0][8].invalid);
+        assert_eq!(gameboard.cells[0][8].value, 0);
+        assert!(history.undo(&mut gameboard));
+        assert_eq!(gameboard, after_note);
+        assert!(history.undo(&mut gameboard));
+        assert_eq!(gameboard, start);
+        assert!(!history.undo(&mut gameboard));
+
+        assert!(history.redo(&mut gameboard));
+        assert!(history.redo(&mut gameboard));
+        assert!(history.redo(&mut gameboard));
+        assert!(gameboard.cells[0][8].invalid);
+        assert!(!history.can_redo());
+    }
+
+    #[test]
+    fn new_move_discards_redo() {
+        let mut gameboard = Gameboard::new();
+        let mut history = History::new();
+        play(&mut history, &mut gameboard, |g| g.set([0, 0], 1));
+        history.undo(&mut gameboard);
+        assert!(history.can_redo());
+        play(&mut history, &mut gameboard, |g| g.set([0, 0], 2));
+        assert!(!history.can_redo());
+        // Moves that change nothing are not recorded.
+        play(&mut history, &mut gameboard, |g| g.set([0, 0], 2));
+        assert!(history.undo(&mut gameboard));
+        assert!(!history.can_undo());
+    }
+
+    #[test]
+    fn round_trips_as_text() {
+        let mut gameboard = Gameboard::new();
+        let mut history = History::new();
+        play(&mut history, &mut gameboard, |g| g.toggle_note([1, 0], 9));
+        play(&mut history, &mut gameboard, |g| g.set([0, 0], 4));
+        play(&mut history, &mut gameboard, |g| g.set([8, 0], 4));
+        history.undo(&mut gameboard);
+
+        let text = history.to_string();
+        assert_eq!(text.lines().count(), 3);
+        assert_eq!(text.parse::<History>(), Ok(history));
+        assert!("u 0,0:1,0,0,0".parse::<History>().is_err());
+        assert!("x 0,0:1,0,0,0>2,0,0,0".parse::<History>().is_err());
+    }
+}
@@ -0,0 +1,374 @@
+//! Key bindings.
+//!
+//! A key map file binds one key per line to an action, on top of the
+//! default bindings. Keys are named as in piston, optionally held with
+//! control or shift:
+//!
+//! ```text
+//! Up up
+//! W up
+//! NumPad5 5
+//! Tab next-empty
+//! ctrl+Z undo
+//! ctrl+shift+Z redo
+//! F1 none
+//! ```
+//!
+//! Binding a key to `none` removes its default binding.
+
+use piston::input::Key;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::read_to_string;
+use std::str::FromStr;
+
+/// Something a key press does.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Action {
+    /// Selects the cell above, wrapping to the bottom row.
+    Up,
+    /// Selects the cell below, wrapping to the top row.
+    Down,
+    /// Selects the cell to the left, wrapping to the last column.
+    Left,
+    /// Selects the cell to the right, wrapping to the first column.
+    Right,
+    /// Selects the next empty cell in reading order, wrapping around.
+    NextEmpty,
+    /// Enters a value, or a pencil mark while taking notes.
+    Value(u8),
+    /// Clears the value of the selected cell.
+    Clear,
+    /// Shows a hint, or explains the one shown.
+    Hint,
+    /// Switches between entering values and pencil marks.
+    Notes,
+    /// Turns highlighting of the selected cell's peers and value on or off.
+    Highlight,
+    /// Switches to the next theme.
+    Theme,
+    /// Shows or hides the statistics.
+    Stats,
+    /// Switches to the next assist level.
+    Assist,
+    /// Reverts the last move.
+    Undo,
+    /// Repeats the last undone move.
+    Redo,
+}
+
+impl Action {
+    /// Actions other than values, with their names.
+    const NAMED: [(Action, &'static str); 14] = [
+        (Action::Up, "up"),
+        (Action::Down, "down"),
+        (Action::Left, "left"),
+        (Action::Right, "right"),
+        (Action::NextEmpty, "next-empty"),
+        (Action::Clear, "clear"),
+        (Action::Hint, "hint"),
+        (Action::Notes, "notes"),
+        (Action::Highlight, "highlight"),
+        (Action::Theme, "theme"),
+        (Action::Stats, "stats"),
+        (Action::Assist, "assist"),
+        (Action::Undo, "undo"),
+        (Action::Redo, "redo"),
+    ];
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Action::Value(val) => write!(f, "{}", val),
+            _ => {
+                let named = Action::NAMED.iter().find(|(a, _)| a == self);
+                f.write_str(named.map(|&(_, name)| name).unwrap_or_default())
+            }
+        }
+    }
+}
+
+impl FromStr for Action {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Action, String> {
+        if let Ok(val) = s.parse::<u8>() {
+            return match val {
+                1..=25 => Ok(Action::Value(val)),
+                _ => Err(format!("value {} is not between 1 and 25", val)),
+            };
+        }
+        Action::NAMED
+            .iter()
+            .find(|&&(_, name)| name == s)
+            .map(|&(action, _)| action)
+            .ok_or_else(|| format!("unknown action `{}`", s))
+    }
+}
+
+/// A key together with the modifiers held down.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Chord {
+    /// Whether a control key is held.
+    pub ctrl: bool,
+    /// Whether a shift key is held.
+    pub shift: bool,
+    /// The key pressed.
+    pub key: Key,
+}
+
+impl Chord {
+    /// The key pressed without modifiers.
+    pub fn key(key: Key) -> Chord {
+        Chord {
+            ctrl: false,
+            shift: false,
+            key,
+        }
+    }
+
+    /// The key pressed with control held.
+    pub fn ctrl(key: Key) -> Chord {
+        Chord {
+            ctrl: true,
+            ..Chord::key(key)
+        }
+    }
+}
+
+impl fmt::Display for Chord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.ctrl {
+            f.write_str("ctrl+")?;
+        }
+        if self.shift {
+            f.write_str("shift+")?;
+        }
+        write!(f, "{:?}", self.key)
+    }
+}
+
+impl FromStr for Chord {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Chord, String> {
+        let mut chord = Chord::key(Key::Unknown);
+        let mut name = s;
+        loop {
+            if let Some(rest) = name.strip_prefix("ctrl+") {
+                chord.ctrl = true;
+                name = rest;
+            } else if let Some(rest) = name.strip_prefix("shift+") {
+                chord.shift = true;
+                name = rest;
+            } else {
+                break;
+            }
+        }
+        // Key codes follow SDL: characters, then scancodes from 2^30.
+        let codes = (0..0x80).chain(0x4000_0000..0x4000_0120);
+        chord.key = codes
+            .map(Key::from)
+            .filter(|&key| key != Key::Unknown)
+            .find(|key| format!("{:?}", key).eq_ignore_ascii_case(name))
+            .ok_or_else(|| format!("unknown key `{}`", name))?;
+        Ok(chord)
+    }
+}
+
+/// Actions bound to keys.
+#[derive(Clone, Debug, PartialEq)]
+pub struct KeyMap {
+    bindings: BTreeMap<Chord, Action>,
+}
+
+impl KeyMap {
+    /// Creates the default bindings: arrows and WASD to move, digits on
+    /// either row or the number pad to enter values, Delete, Backspace or 0
+    /// to clear, Tab for the next empty cell, H for a hint, N for notes, L
+    /// for highlighting, T for the next theme, I for statistics, C for the
+    /// next assist level and Ctrl+Z or Ctrl+Y to undo and redo.
+    pub fn new() -> KeyMap {
+        use piston::input::Key::*;
+
+        let mut keymap = KeyMap {
+            bindings: BTreeMap::new(),
+        };
+        let moves = [
+            (Up, W, Action::Up),
+            (Down, S, Action::Down),
+            (Left, A, Action::Left),
+            (Right, D, Action::Right),
+        ];
+        for &(arrow, letter, action) in moves.iter() {
+            keymap.bind(Chord::key(arrow), action);
+            keymap.bind(Chord::key(letter), action);
+        }
+        let digits = [D1, D2, D3, D4, D5, D6, D7, D8, D9];
+        let numpad = [
+            NumPad1, NumPad2, NumPad3, NumPad4, NumPad5, NumPad6, NumPad7,
+            NumPad8, NumPad9,
+        ];
+        for (k, (&digit, &pad)) in digits.iter().zip(numpad.iter()).enumerate()
+        {
+            keymap.bind(Chord::key(digit), Action::Value(k as u8 + 1));
+            keymap.bind(Chord::key(pad), Action::Value(k as u8 + 1));
+        }
+        for &key in [Delete, Backspace, D0, NumPad0].iter() {
+            keymap.bind(Chord::key(key), Action::Clear);
+        }
+        keymap.bind(Chord::key(Tab), Action::NextEmpty);
+        keymap.bind(Chord::key(H), Action::Hint);
+        keymap.bind(Chord::key(N), Action::Notes);
+        keymap.bind(Chord::key(L), Action::Highlight);
+        keymap.bind(Chord::key(T), Action::Theme);
+        keymap.bind(Chord::key(I), Action::Stats);
+        keymap.bind(Chord::key(C), Action::Assist);
+        keymap.bind(Chord::ctrl(Z), Action::Undo);
+        keymap.bind(Chord::ctrl(Y), Action::Redo);
+        keymap.bind(
+            Chord {
+                shift: true,
+                ..Chord::ctrl(Z)
+            },
+            Action::Redo,
+        );
+        keymap
+    }
+
+    /// Reads the key map file `filename`, on top of the default bindings.
+    pub fn load(filename: &str) -> Result<KeyMap, String> {
+        let data = read_to_string(filename).map_err(|e| {
+            format!("failed to read key map file `{}`: {}", filename, e)
+        })?;
+        let mut keymap = KeyMap::new();
+        keymap.parse(&data).map_err(|e| {
+            format!("invalid key map file `{}`: {}", filename, e)
+        })?;
+        Ok(keymap)
+    }
+
+    /// Applies bindings written one per line, skipping blank lines and `#`
+    /// comments.
+    pub fn parse(&mut self, data: &str) -> Result<(), String> {
+        for (i, line) in data.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let binding = match line.split_whitespace().collect::<Vec<_>>()[..]
+            {
+                [chord, "none"] => chord.parse().map(|chord| (chord, None)),
+                [chord, action] => chord
+                    .parse()
+                    .and_then(|chord| Ok((chord, Some(action.parse()?)))),
+                _ => Err("expected a key and an action".into()),
+            };
+            match binding.map_err(|e| format!("line {}: {}", i + 1, e))? {
+                (chord, Some(action)) => self.bind(chord, action),
+                (chord, None) => self.unbind(chord),
+            }
+        }
+        Ok(())
+    }
+
+    /// Binds `chord` to `action`, replacing any previous binding.
+    pub fn bind(&mut self, chord: Chord, action: Action) {
+        self.bindings.insert(chord, action);
+    }
+
+    /// Removes the binding of `chord`.
+    pub fn unbind(&mut self, chord: Chord) {
+        self.bindings.remove(&chord);
+    }
+
+    /// The action bound to `chord`.
+    ///
+    /// Shift also enters pencil marks, so a chord with shift held falls back
+    /// to the binding without it.
+    pub fn action(&self, chord: Chord) -> Option<Action> {
+        let unshifted = Chord {
+            shift: false,
+            ..chord
+        };
+        let bindings = &self.bindings;
+        bindings
+            .get(&chord)
+            .or_else(|| bindings.get(&unshifted))
+            .copied()
+    }
+}
+
+impl Default for KeyMap {
+    fn default() -> KeyMap {
+        KeyMap::new()
+    }
+}
+
+/// Writes the bindings in the key map file format.
+impl fmt::Display for KeyMap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (chord, action) in self.bindings.iter() {
+            writeln!(f, "{} {}", chord, action)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_bindings() {
+        let keymap = KeyMap::new();
+        let action = |chord| keymap.action(chord);
+        assert_eq!(action(Chord::key(Key::W)), Some(Action::Up));
+        assert_eq!(action(Chord::key(Key::Right)), Some(Action::Right));
+        assert_eq!(action(Chord::key(Key::NumPad7)), Some(Action::Value(7)));
+        assert_eq!(action(Chord::key(Key::D0)), Some(Action::Clear));
+        assert_eq!(action(Chord::key(Key::Tab)), Some(Action::NextEmpty));
+        assert_eq!(action(Chord::key(Key::L)), Some(Action::Highlight));
+        assert_eq!(action(Chord::ctrl(Key::Z)), Some(Action::Undo));
+        let shifted = |key| Chord {
+            shift: true,
+            ..Chord::ctrl(key)
+        };
+        assert_eq!(action(shifted(Key::Z)), Some(Action::Redo));
+        assert_eq!(action(shifted(Key::Y)), Some(Action::Redo));
+        assert_eq!(action(Chord::key(Key::Z)), None);
+    }
+
+    #[test]
+    fn parses_bindings() {
+        let mut keymap = KeyMap::new();
+        let data = "# vi keys\nK up\nshift+J down\nctrl+u undo\n\nW none\n";
+        keymap.parse(data).unwrap();
+        assert_eq!(keymap.action(Chord::key(Key::K)), Some(Action::Up));
+        assert_eq!(keymap.action(Chord::ctrl(Key::U)), Some(Action::Undo));
+        assert_eq!(keymap.action(Chord::key(Key::W)), None);
+        assert_eq!(keymap.action(Chord::key(Key::J)), None);
+        let chord: Chord = "shift+J".parse().unwrap();
+        assert_eq!(keymap.action(chord), Some(Action::Down));
+
+        let text = keymap.to_string();
+        assert!(text.contains("NumPad3 3\n"));
+        assert!(text.contains("ctrl+shift+Z redo\n"));
+        let mut again = KeyMap::new();
+        again.parse(&text).unwrap();
+        again.unbind(Chord::key(Key::W));
+        assert_eq!(again, keymap);
+    }
+
+    #[test]
+    fn reports_bad_bindings() {
+        let mut keymap = KeyMap::new();
+        let error = |data| keymap.clone().parse(data).unwrap_err();
+        assert_eq!(error("\nFoo up"), "line 2: unknown key `Foo`");
+        assert_eq!(error("A jump"), "line 1: unknown action `jump`");
+        assert_eq!(error("A 26"), "line 1: value 26 is not between 1 and 25");
+        assert_eq!(error("A"), "line 1: expected a key and an action");
+        assert!(keymap.parse("ctrl+A up right").is_err());
+    }
+}
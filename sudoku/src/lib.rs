@@ -0,0 +1,46 @@
+#![deny(missing_docs)]
+
+//! Sudoku boards, variant rules, solving, grading and puzzle files.
+//!
+//! The window, its controller and its view are only built with the `gui`
+//! feature, which pulls in piston and OpenGL.
+
+pub use crate::gameboard::{Assist, Gameboard, Geometry};
+pub use crate::generator::Difficulty;
+pub use crate::rules::Rules;
+pub use crate::save::SavedGame;
+pub use crate::solver::Uniqueness;
+pub use crate::stats::Stats;
+
+#[cfg(feature = "gui")]
+pub use crate::gameboard_controller::GameboardController;
+#[cfg(feature = "gui")]
+pub use crate::gameboard_view::{GameboardView, GameboardViewSettings};
+#[cfg(feature = "gui")]
+pub use crate::keymap::KeyMap;
+#[cfg(feature = "gui")]
+pub use crate::theme::Theme;
+
+pub mod cli;
+pub mod formats;
+pub mod gameboard;
+pub mod generator;
+pub mod grader;
+pub mod history;
+pub mod rules;
+pub mod save;
+pub mod sdm;
+pub mod solver;
+pub mod stats;
+
+#[cfg(test)]
+mod corpus;
+
+#[cfg(feature = "gui")]
+pub mod gameboard_controller;
+#[cfg(feature = "gui")]
+pub mod gameboard_view;
+#[cfg(feature = "gui")]
+pub mod keymap;
+#[cfg(feature = "gui")]
+pub mod theme;
@@ -2,24 +2,172 @@
 
 //! A Sudoku game.
 
+#[cfg(feature = "gui")]
 use glutin_window::GlutinWindow;
+#[cfg(feature = "gui")]
 use opengl_graphics::{
     Filter, GlGraphics, GlyphCache, OpenGL, TextureSettings,
 };
+#[cfg(feature = "gui")]
 use piston::event_loop::{EventSettings, Events};
+#[cfg(feature = "gui")]
 use piston::{EventLoop, RenderEvent, WindowSettings};
+#[cfg(feature = "gui")]
+use std::io::ErrorKind;
 
-pub use crate::gameboard::Gameboard;
-pub use crate::gameboard_controller::GameboardController;
-pub use crate::gameboard_view::{GameboardView, GameboardViewSettings};
-
-mod gameboard;
-mod gameboard_controller;
-mod gameboard_view;
+use sudoku::cli;
+#[cfg(feature = "gui")]
+use sudoku::{
+    formats, solver, Gameboard, GameboardController, GameboardView, KeyMap,
+    Rules, SavedGame, Stats, Theme, Uniqueness,
+};
 
+#[cfg(feature = "gui")]
 static FONT: &str = "/usr/share/fonts/TTF/DejaVuSans.ttf";
 
+/// Where the game in progress is saved on exit.
+#[cfg(feature = "gui")]
+static SAVE_FILE: &str = "sudoku.save";
+
+/// Where the statistics of finished games are kept.
+#[cfg(feature = "gui")]
+static STATS_FILE: &str = "sudoku-stats.json";
+
+static USAGE: &str = "usage: sudoku PUZZLE_FILE [--rules FILE] [OPTIONS]
+       sudoku --resume [OPTIONS]
+       sudoku solve|check|grade PUZZLE_FILE [--rules FILE] [--json]
+       sudoku convert PUZZLE_FILE OUT_FILE [--json]
+       sudoku generate DIFFICULTY [--size SHAPE] [--seed SEED] [OUT_FILE]
+
+Puzzle files may be SDM, SDK, SS, one-line or OpenSudoku XML. Only the first
+puzzle of a collection is played.
+
+A rules file adds variant rules to the puzzle, one per line: `diagonals`,
+`anti-knight`, `anti-king`, `cage SUM CELLS...`, `thermo CELLS...` from the
+bulb, or `jigsaw REGIONS` giving the region of every cell. Cells are written
+as `r1c1` for the top left.
+
+The commands work without a window. solve prints the values of every puzzle
+in a file, check reports whether each has exactly one solution, grade names
+the difficulty and hardest technique, convert writes the puzzles in the
+format named by the extension of OUT_FILE and generate makes a new puzzle.
+--json prints JSON instead of text. They exit with 0 on success, 1 when a
+puzzle has no solution or more than one or generate settles for another
+difficulty, and 2 on bad arguments or files.
+
+SHAPE is a box size like 3x2, or the side of the board like 16, from 4 up to
+25. The default is 9.
+
+Arrows or WASD move the selection, digits on either row or the number pad
+enter values, and Delete, Backspace or 0 clears a cell. Tab jumps to the next
+empty cell, and L turns highlighting of the selected cell's row, column, box
+and value on or off. A keys file rebinds keys one per line, like `K up`,
+`ctrl+U undo` or `W none`. I shows the statistics of finished games.
+
+Click a cell to select it. Clicking a digit on the number pad beside the
+board enters it, dragging it onto a cell enters it there, right-clicking it
+toggles the pencil mark and the mouse wheel steps through the values.
+
+OPTIONS are `--keys FILE` to load a keys file and `--theme THEME` to pick the
+light, dark or high-contrast theme, or a theme file setting colors, line
+widths, font sizes and the font. T switches to the next theme while playing.
+
+`--assist LEVEL` picks how wrong values are pointed out: `none`, `conflicts`
+with other cells, the default, or `solution` to check every value against
+the solution. C switches to the next level while playing. `--mistakes N`
+ends the game after N mistakes.";
+
 fn main() {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    // `--generate` was the way to generate puzzles before the commands.
+    if let Some(k) = args.iter().position(|arg| arg == "--generate") {
+        args.remove(k);
+        args.insert(0, "generate".into());
+    }
+    if args
+        .first()
+        .is_some_and(|arg| cli::COMMANDS.contains(&&arg[..]))
+    {
+        run_command(&args);
+    }
+    play(args);
+}
+
+/// Without the `gui` feature there is no window to play in.
+#[cfg(not(feature = "gui"))]
+fn play(_args: Vec<String>) {
+    eprintln!(
+        "sudoku was built without the `gui` feature, only the commands \
+         work\n\n{}",
+        USAGE
+    );
+    std::process::exit(cli::ERROR)
+}
+
+/// Opens a window to play the puzzle picked by `args`.
+#[cfg(feature = "gui")]
+fn play(args: Vec<String>) {
+    let mut infile = None;
+    let mut rules_file = None;
+    let mut keys_file = None;
+    let mut theme_name = None;
+    let mut assist = None;
+    let mut mistake_limit = None;
+    let mut resume = false;
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--rules" => rules_file = Some(args.next().expect(USAGE)),
+            "--keys" => keys_file = Some(args.next().expect(USAGE)),
+            "--theme" => theme_name = Some(args.next().expect(USAGE)),
+            "--assist" => {
+                let level = args.next().expect(USAGE);
+                assist = Some(level.parse().unwrap_or_else(|e| fail(e)));
+            }
+            "--mistakes" => {
+                let limit = args.next().expect(USAGE);
+                mistake_limit = Some(limit.parse().unwrap_or_else(|_| {
+                    fail(format!("invalid number of mistakes `{}`", limit))
+                }));
+            }
+            "--resume" => resume = true,
+            _ => infile = Some(arg),
+        }
+    }
+
+    let mut gameboard_controller = if resume {
+        let saved = SavedGame::load(SAVE_FILE).unwrap_or_else(|e| fail(e));
+        GameboardController::resume(saved)
+    } else {
+        let puzzle = load_puzzle(&infile.expect(USAGE), rules_file.as_deref());
+        GameboardController::new(puzzle)
+    };
+    if let Some(assist) = assist {
+        gameboard_controller.assist = assist;
+    }
+    if mistake_limit.is_some() {
+        gameboard_controller.mistake_limit = mistake_limit;
+    }
+    if let Some(keys_file) = keys_file {
+        gameboard_controller.keymap =
+            KeyMap::load(&keys_file).unwrap_or_else(|e| fail(e));
+    }
+    let mut themes = Theme::bundled();
+    let mut theme = 0;
+    if let Some(theme_name) = theme_name {
+        let chosen = Theme::find(&theme_name).unwrap_or_else(|e| fail(e));
+        theme = match themes.iter().position(|theme| *theme == chosen) {
+            Some(theme) => theme,
+            None => {
+                themes.push(chosen);
+                themes.len() - 1
+            }
+        };
+    }
+    gameboard_controller.theme = theme;
+    let (stats, stats_file) = load_stats();
+    gameboard_controller.stats = stats;
+
     let opengl = OpenGL::V3_2;
     let settings = WindowSettings::new("Sudoku", (640, 480))
         .exit_on_esc(true)
@@ -27,20 +175,13 @@ fn main() {
         .vsync(true);
     let mut window: GlutinWindow =
         settings.build().expect("Could not create window");
-    let mut events = Events::new(EventSettings::new().lazy(true));
+    // Redraw often enough to keep the clock ticking.
+    let mut events = Events::new(EventSettings::new().max_fps(30).ups(4));
     let mut gl = GlGraphics::new(opengl);
 
-    let args: Vec<_> = std::env::args().collect();
-    let infile = args.get(1).expect("usage: sudoku SDM_FILE");
-
-    let gameboard = Gameboard::load_sdm(infile);
-    let mut gameboard_controller = GameboardController::new(gameboard);
-    let gameboard_view_settings = GameboardViewSettings::new();
-    let gameboard_view = GameboardView::new(gameboard_view_settings);
-
-    let texture_settings = TextureSettings::new().filter(Filter::Nearest);
-    let ref mut glyphs = GlyphCache::new(FONT, (), texture_settings)
-        .expect(&format!("failed to load font `{}`", FONT));
+    let gameboard_view_settings = themes[theme].settings.clone();
+    let mut gameboard_view = GameboardView::new(gameboard_view_settings);
+    let mut glyphs = load_font(&themes[theme]);
 
     while let Some(e) = events.next(&mut window) {
         gameboard_controller.event(
@@ -48,13 +189,138 @@ fn main() {
             gameboard_view.settings.size,
             &e,
         );
+        if gameboard_controller.theme != theme {
+            let next = gameboard_controller.theme % themes.len();
+            if themes[next].font != themes[theme].font {
+                glyphs = load_font(&themes[next]);
+            }
+            themes[next].apply(&mut gameboard_view.settings);
+            theme = next;
+            gameboard_controller.theme = next;
+        }
         if let Some(args) = e.render_args() {
+            gameboard_view
+                .settings
+                .fit(args.window_size, args.draw_size);
             gl.draw(args.viewport(), |c, g| {
                 use graphics::clear;
 
-                clear([1.0; 4], g);
-                gameboard_view.draw(&gameboard_controller, glyphs, &c, g);
+                clear(gameboard_view.settings.window_background_color, g);
+                gameboard_view.draw(&gameboard_controller, &mut glyphs, &c, g);
             });
         }
     }
+
+    autosave(&gameboard_controller, stats_file);
+}
+
+/// Loads the first puzzle in `infile` with the variant rules in
+/// `rules_file`, rejecting ones without a unique solution.
+#[cfg(feature = "gui")]
+fn load_puzzle(infile: &str, rules_file: Option<&str>) -> Gameboard {
+    let puzzles = formats::load(infile).unwrap_or_else(|e| fail(e));
+    let mut gameboard =
+        puzzles.into_iter().next().expect("at least one puzzle");
+    if let Some(rules_file) = rules_file {
+        gameboard.rules = Rules::load(rules_file, gameboard.geometry)
+            .unwrap_or_else(|e| fail(e));
+    }
+    match solver::uniqueness(&gameboard) {
+        Uniqueness::Unique => gameboard,
+        Uniqueness::Unsolvable => {
+            fail(format!("puzzle `{}` has no solution", infile))
+        }
+        Uniqueness::Ambiguous => {
+            fail(format!("puzzle `{}` has more than one solution", infile))
+        }
+    }
+}
+
+/// Loads the font of `theme`, or the default font if it names none.
+#[cfg(feature = "gui")]
+fn load_font(theme: &Theme) -> GlyphCache<'static> {
+    let font = theme.font.as_deref().unwrap_or(FONT);
+    let texture_settings = TextureSettings::new().filter(Filter::Nearest);
+    GlyphCache::new(font, (), texture_settings).unwrap_or_else(|e| {
+        fail(format!("failed to load font `{}`: {}", font, e))
+    })
+}
+
+/// Loads the statistics of finished games and the file to save them to.
+///
+/// A statistics file that cannot be used is moved aside with a warning and
+/// play starts without statistics. If it cannot be moved, the statistics
+/// are not saved, so the file is never overwritten.
+#[cfg(feature = "gui")]
+fn load_stats() -> (Stats, Option<&'static str>) {
+    let e = match Stats::load(STATS_FILE) {
+        Ok(stats) => return (stats, Some(STATS_FILE)),
+        Err(e) => e,
+    };
+    let backup = format!("{}.bak", STATS_FILE);
+    match std::fs::rename(STATS_FILE, &backup) {
+        Ok(()) => {
+            eprintln!("warning: {}, moved it to `{}`", e, backup);
+            (Stats::default(), Some(STATS_FILE))
+        }
+        Err(err) => {
+            eprintln!(
+                "warning: {}, and failed to move it to `{}`: {}; statistics \
+                 will not be saved",
+                e, backup, err
+            );
+            (Stats::default(), None)
+        }
+    }
+}
+
+/// Saves an unfinished game so it can be resumed, or forgets the saved game
+/// once it is over, recording the statistics to `stats_file` if the puzzle
+/// was completed.
+#[cfg(feature = "gui")]
+fn autosave(
+    gameboard_controller: &GameboardController,
+    stats_file: Option<&str>,
+) {
+    if let (Some(_), Some(stats_file)) =
+        (gameboard_controller.finished_game(), stats_file)
+    {
+        if let Err(e) = gameboard_controller.stats.save(stats_file) {
+            eprintln!("failed to save statistics to `{}`: {}", stats_file, e);
+        }
+    }
+    let result = if gameboard_controller.game_over() {
+        match std::fs::remove_file(SAVE_FILE) {
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+            result => result,
+        }
+    } else {
+        gameboard_controller.saved_game().save(SAVE_FILE)
+    };
+    if let Err(e) = result {
+        eprintln!("failed to save game to `{}`: {}", SAVE_FILE, e);
+    }
+}
+
+/// Runs a command that needs no window, exiting with its code.
+fn run_command(args: &[String]) -> ! {
+    match cli::run(args) {
+        Ok(output) => {
+            print!("{}", output.stdout);
+            eprint!("{}", output.stderr);
+            std::process::exit(output.code)
+        }
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            std::process::exit(cli::ERROR)
+        }
+    }
+}
+
+/// Reports `message` and exits with `cli::ERROR`, as the commands do for bad
+/// arguments and files.
+#[cfg(feature = "gui")]
+fn fail(message: impl std::fmt::Display) -> ! {
+    eprintln!("{}", message);
+    std::process::exit(cli::ERROR)
 }
@@ -0,0 +1,719 @@
+//! Variant rules adding to or replacing the classic constraints.
+//!
+//! Every board has each value once in every row, column and box. Variants
+//! add rules on top, such as diagonals or killer cages, or replace the boxes
+//! with irregular regions. Rules are written one per line, naming cells as
+//! `r1c1` for the top left:
+//!
+//! ```text
+//! diagonals
+//! anti-knight
+//! anti-king
+//! cage 15 r1c1 r1c2 r2c1
+//! thermo r5c5 r5c6 r4c6
+//! jigsaw 111222333111222333...
+//! ```
+//!
+//! A jigsaw rule gives the region of every cell, row by row, written as a
+//! value from `1` to the size of the board.
+
+use std::fmt;
+use std::fs::read_to_string;
+use std::sync::Arc;
+
+use crate::gameboard::{char_value, value_char, Geometry};
+
+/// A constraint on the values a board may hold.
+pub trait Rule: fmt::Debug + fmt::Display + Send + Sync {
+    /// Whether the rule's regions take the place of the boxes.
+    fn replaces_boxes(&self) -> bool {
+        false
+    }
+
+    /// Groups of cells that may not repeat a value.
+    fn units(&self, _geometry: Geometry) -> Vec<Vec<[usize; 2]>> {
+        Vec::new()
+    }
+
+    /// Cells that may not hold the same value as `ind`, besides those
+    /// sharing a unit with it.
+    fn peers(&self, _geometry: Geometry, _ind: [usize; 2]) -> Vec<[usize; 2]> {
+        Vec::new()
+    }
+
+    /// Whether `val` fits at `ind` given the other `values`, indexed by row
+    /// then column with `0` for empty cells. Any value already at `ind` is
+    /// ignored.
+    ///
+    /// Rules that only forbid repeats leave this to `units` and `peers`.
+    fn allows(&self, _values: &[Vec<u8>], _ind: [usize; 2], _val: u8) -> bool {
+        true
+    }
+
+    /// Shapes showing the rule on the board.
+    fn marks(&self, geometry: Geometry) -> Vec<Mark>;
+}
+
+/// A shape drawn on the board to show a rule.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Mark {
+    /// A line through the centers of the cells.
+    Line(Vec<[usize; 2]>),
+    /// A thermometer through the centers of the cells, with the bulb on the
+    /// first one.
+    Thermometer(Vec<[usize; 2]>),
+    /// An outline around the cells, with their sum in the first one.
+    Cage {
+        /// Cells inside the cage.
+        cells: Vec<[usize; 2]>,
+        /// Sum of the values in the cage.
+        sum: u32,
+    },
+    /// Thick edges between cells of different regions, given as the region
+    /// of every cell, indexed by row then column.
+    Regions(Vec<Vec<usize>>),
+    /// A rule with no place on the board, named next to it.
+    Label(&'static str),
+}
+
+/// The variant rules of a board, on top of its rows and columns.
+#[derive(Clone, Debug, Default)]
+pub struct Rules(Vec<Arc<dyn Rule>>);
+
+impl Rules {
+    /// Reads the rules for a board shaped like `geometry` from `filename`.
+    pub fn load(filename: &str, geometry: Geometry) -> Result<Rules, String> {
+        let data = read_to_string(filename).map_err(|e| {
+            format!("failed to read rules file `{}`: {}", filename, e)
+        })?;
+        Rules::parse(&data, geometry)
+            .map_err(|e| format!("invalid rules file `{}`: {}", filename, e))
+    }
+
+    /// Parses rules written one per line, skipping blank lines and `#`
+    /// comments.
+    pub fn parse(data: &str, geometry: Geometry) -> Result<Rules, String> {
+        let mut rules = Rules::default();
+        for (i, line) in data.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let rule = parse_rule(line, geometry)
+                .map_err(|e| format!("line {}: {}", i + 1, e))?;
+            rules.push(rule);
+        }
+        Ok(rules)
+    }
+
+    /// Adds `rule`.
+    pub fn push(&mut self, rule: Arc<dyn Rule>) {
+        self.0.push(rule);
+    }
+
+    /// Whether there are no variant rules, leaving a classic board.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Iterates over the rules.
+    pub fn iter(&self) -> impl Iterator<Item = &dyn Rule> {
+        self.0.iter().map(|rule| &**rule)
+    }
+
+    /// Whether a rule replaces the boxes of the board.
+    pub fn replaces_boxes(&self) -> bool {
+        self.iter().any(|rule| rule.replaces_boxes())
+    }
+
+    /// All groups of cells that may not repeat a value: rows, columns, boxes
+    /// unless a rule replaces them, and the units of every rule.
+    pub fn units(&self, geometry: Geometry) -> Vec<Vec<[usize; 2]>> {
+        let size = geometry.size();
+        let boxes = !self.replaces_boxes();
+        let mut units = Vec::new();
+        for n in 0..size {
+            units.push((0..size).map(|k| [k, n]).collect());
+            units.push((0..size).map(|k| [n, k]).collect());
+            if boxes {
+                units
+                    .push((0..size).map(|k| geometry.box_cell(n, k)).collect());
+            }
+        }
+        for rule in self.iter() {
+            units.extend(rule.units(geometry));
+        }
+        units
+    }
+
+    /// Cells that may not hold the same value as `ind`, each listed once.
+    pub fn peers(
+        &self,
+        geometry: Geometry,
+        ind: [usize; 2],
+    ) -> Vec<[usize; 2]> {
+        let units = self.units(geometry);
+        let units = units.into_iter().filter(|unit| unit.contains(&ind));
+        let mut peers: Vec<[usize; 2]> = units.flatten().collect();
+        for rule in self.iter() {
+            peers.extend(rule.peers(geometry, ind));
+        }
+        peers.sort_unstable();
+        peers.dedup();
+        peers.retain(|&peer| peer != ind);
+        peers
+    }
+
+    /// Works out the units and peers of every cell for solving.
+    pub fn layout(&self, geometry: Geometry) -> Layout {
+        let size = geometry.size();
+        let units = self.units(geometry);
+        let mut cell_units = vec![vec![Vec::new(); size]; size];
+        for (n, unit) in units.iter().enumerate() {
+            for &[j, i] in unit {
+                cell_units[i][j].push(n);
+            }
+        }
+        let mut peers = vec![vec![Vec::new(); size]; size];
+        for rule in self.iter() {
+            for (i, row) in peers.iter_mut().enumerate() {
+                for (j, peers) in row.iter_mut().enumerate() {
+                    peers.extend(rule.peers(geometry, [j, i]));
+                }
+            }
+        }
+        Layout {
+            units,
+            cell_units,
+            peers,
+        }
+    }
+
+    /// Whether every rule allows `val` at `ind` given the other `values`.
+    pub fn allows(&self, values: &[Vec<u8>], ind: [usize; 2], val: u8) -> bool {
+        self.iter().all(|rule| rule.allows(values, ind, val))
+    }
+}
+
+/// The units and peers of every cell of a board.
+#[derive(Clone, Debug)]
+pub struct Layout {
+    /// Groups of cells that may not repeat a value.
+    pub units: Vec<Vec<[usize; 2]>>,
+    /// Indices into `units` of the units holding each cell, indexed by row
+    /// then column.
+    pub cell_units: Vec<Vec<Vec<usize>>>,
+    /// Cells that may not share a value with each cell without sharing a
+    /// unit, indexed by row then column.
+    pub peers: Vec<Vec<Vec<[usize; 2]>>>,
+}
+
+/// Rules are equal when they are written the same way.
+impl PartialEq for Rules {
+    fn eq(&self, other: &Rules) -> bool {
+        self.to_string() == other.to_string()
+    }
+}
+
+/// Writes one rule per line, in the format read by `Rules::parse`.
+impl fmt::Display for Rules {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for rule in self.iter() {
+            writeln!(f, "{}", rule)?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses a single rule for a board shaped like `geometry`.
+pub fn parse_rule(
+    line: &str,
+    geometry: Geometry,
+) -> Result<Arc<dyn Rule>, String> {
+    let mut words = line.split_whitespace();
+    let name = words.next().unwrap_or_default();
+    let args: Vec<&str> = words.collect();
+    let size = geometry.size();
+    let cells = |args: &[&str]| {
+        args.iter()
+            .map(|arg| parse_cell(arg, size))
+            .collect::<Result<Vec<_>, String>>()
+    };
+    let no_args = || match args[..] {
+        [] => Ok(()),
+        _ => Err(format!("`{}` takes no arguments", name)),
+    };
+    let rule: Arc<dyn Rule> = match name {
+        "diagonals" => no_args().map(|_| Arc::new(Diagonals))?,
+        "anti-knight" => no_args().map(|_| Arc::new(AntiKnight))?,
+        "anti-king" => no_args().map(|_| Arc::new(AntiKing))?,
+        "cage" => {
+            let (sum, cells) = match args.split_first() {
+                Some((sum, rest)) if !rest.is_empty() => (sum, cells(rest)?),
+                _ => return Err("a cage needs a sum and cells".into()),
+            };
+            let sum = sum
+                .parse()
+                .map_err(|_| format!("invalid cage sum `{}`", sum))?;
+            Arc::new(Cage::new(cells, sum, size)?)
+        }
+        "thermo" => Arc::new(Thermometer::new(cells(&args)?, size)?),
+        "jigsaw" => match args[..] {
+            [regions] => Arc::new(Jigsaw::parse(regions, size)?),
+            _ => return Err("a jigsaw needs the region of every cell".into()),
+        },
+        _ => return Err(format!("unknown rule `{}`", name)),
+    };
+    Ok(rule)
+}
+
+/// Parses a cell written as `r1c1`, counting from the top left.
+fn parse_cell(s: &str, size: usize) -> Result<[usize; 2], String> {
+    let error = || format!("invalid cell `{}`", s);
+    let (row, col) = s
+        .strip_prefix('r')
+        .and_then(|s| s.split_once('c'))
+        .ok_or_else(error)?;
+    let row: usize = row.parse().map_err(|_| error())?;
+    let col: usize = col.parse().map_err(|_| error())?;
+    if !(1..=size).contains(&row) || !(1..=size).contains(&col) {
+        return Err(format!("cell `{}` is off the board", s));
+    }
+    Ok([col - 1, row - 1])
+}
+
+/// Writes `cells` as in `r1c1 r1c2`.
+fn write_cells(f: &mut fmt::Formatter, cells: &[[usize; 2]]) -> fmt::Result {
+    for &[j, i] in cells {
+        write!(f, " r{}c{}", i + 1, j + 1)?;
+    }
+    Ok(())
+}
+
+/// Checks that no cell appears twice in `cells`.
+fn distinct(cells: &[[usize; 2]]) -> Result<(), String> {
+    for (k, cell) in cells.iter().enumerate() {
+        if cells[..k].contains(cell) {
+            return Err(format!(
+                "cell r{}c{} is repeated",
+                cell[1] + 1,
+                cell[0] + 1
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Both main diagonals hold every value once, as in X-Sudoku.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Diagonals;
+
+impl Diagonals {
+    fn lines(geometry: Geometry) -> Vec<Vec<[usize; 2]>> {
+        let size = geometry.size();
+        vec![
+            (0..size).map(|k| [k, k]).collect(),
+            (0..size).map(|k| [size - 1 - k, k]).collect(),
+        ]
+    }
+}
+
+impl Rule for Diagonals {
+    fn units(&self, geometry: Geometry) -> Vec<Vec<[usize; 2]>> {
+        Diagonals::lines(geometry)
+    }
+
+    fn marks(&self, geometry: Geometry) -> Vec<Mark> {
+        Diagonals::lines(geometry)
+            .into_iter()
+            .map(Mark::Line)
+            .collect()
+    }
+}
+
+impl fmt::Display for Diagonals {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("diagonals")
+    }
+}
+
+/// Irregular regions holding every value once, in place of the boxes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Jigsaw {
+    /// Region of every cell, indexed by row then column.
+    pub regions: Vec<Vec<usize>>,
+}
+
+impl Jigsaw {
+    /// Parses the region of every cell, row by row, each written as a value
+    /// from `1` to `size`.
+    pub fn parse(s: &str, size: usize) -> Result<Jigsaw, String> {
+        let ids = s
+            .chars()
+            .map(|c| match char_value(c) {
+                Some(id) if id as usize <= size => Ok(id as usize - 1),
+                _ => Err(format!("invalid region `{}`", c)),
+            })
+            .collect::<Result<Vec<usize>, String>>()?;
+        if ids.len() != size * size {
+            return Err(format!(
+                "expected the regions of {} cells, found {}",
+                size * size,
+                ids.len()
+            ));
+        }
+        let regions: Vec<Vec<usize>> =
+            ids.chunks(size).map(|row| row.to_vec()).collect();
+        for id in 0..size {
+            let count = ids.iter().filter(|&&other| other == id).count();
+            if count != size {
+                return Err(format!(
+                    "region {} has {} cells instead of {}",
+                    value_char(id as u8 + 1).unwrap_or('?'),
+                    count,
+                    size
+                ));
+            }
+        }
+        Ok(Jigsaw { regions })
+    }
+}
+
+impl Rule for Jigsaw {
+    fn replaces_boxes(&self) -> bool {
+        true
+    }
+
+    fn units(&self, geometry: Geometry) -> Vec<Vec<[usize; 2]>> {
+        let size = geometry.size();
+        let mut units = vec![Vec::new(); size];
+        for (i, row) in self.regions.iter().enumerate() {
+            for (j, &id) in row.iter().enumerate() {
+                units[id].push([j, i]);
+            }
+        }
+        units
+    }
+
+    fn marks(&self, _geometry: Geometry) -> Vec<Mark> {
+        vec![Mark::Regions(self.regions.clone())]
+    }
+}
+
+impl fmt::Display for Jigsaw {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("jigsaw ")?;
+        for &id in self.regions.iter().flatten() {
+            write!(f, "{}", value_char(id as u8 + 1).unwrap_or('?'))?;
+        }
+        Ok(())
+    }
+}
+
+/// A killer cage: its cells add up to `sum` and do not repeat a value.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cage {
+    /// Cells inside the cage.
+    pub cells: Vec<[usize; 2]>,
+    /// Sum of the values in the cage.
+    pub sum: u32,
+}
+
+impl Cage {
+    /// Creates a cage, checking that its cells are distinct and few enough
+    /// not to repeat a value on a board of `size`.
+    pub fn new(
+        cells: Vec<[usize; 2]>,
+        sum: u32,
+        size: usize,
+    ) -> Result<Cage, String> {
+        if cells.len() > size {
+            return Err(format!("a cage holds at most {} cells", size));
+        }
+        distinct(&cells)?;
+        Ok(Cage { cells, sum })
+    }
+}
+
+impl Rule for Cage {
+    fn units(&self, _geometry: Geometry) -> Vec<Vec<[usize; 2]>> {
+        vec![self.cells.clone()]
+    }
+
+    /// Checks that the cage can still reach its sum with the values left.
+    fn allows(&self, values: &[Vec<u8>], ind: [usize; 2], val: u8) -> bool {
+        if !self.cells.contains(&ind) {
+            return true;
+        }
+        let size = values.len() as u32;
+        let mut total = val as u32;
+        let mut empty = 0;
+        for &[j, i] in &self.cells {
+            match values[i][j] {
+                _ if [j, i] == ind => {}
+                0 => empty += 1,
+                other => total += other as u32,
+            }
+        }
+        // The empty cells add at least 1 + 2 + ... and at most the same
+        // number of the largest values.
+        let least = empty * (empty + 1) / 2;
+        let most = empty * (2 * size + 1).saturating_sub(empty) / 2;
+        total + least <= self.sum && self.sum <= total + most
+    }
+
+    fn marks(&self, _geometry: Geometry) -> Vec<Mark> {
+        vec![Mark::Cage {
+            cells: self.cells.clone(),
+            sum: self.sum,
+        }]
+    }
+}
+
+impl fmt::Display for Cage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "cage {}", self.sum)?;
+        write_cells(f, &self.cells)
+    }
+}
+
+/// Values rise strictly from the bulb of the thermometer to its tip.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Thermometer {
+    /// Cells from the bulb to the tip.
+    pub cells: Vec<[usize; 2]>,
+}
+
+impl Thermometer {
+    /// Creates a thermometer, checking that its values can all rise on a
+    /// board of `size`.
+    pub fn new(
+        cells: Vec<[usize; 2]>,
+        size: usize,
+    ) -> Result<Thermometer, String> {
+        if cells.len() < 2 || cells.len() > size {
+            return Err(format!(
+                "a thermometer needs from 2 to {} cells",
+                size
+            ));
+        }
+        distinct(&cells)?;
+        Ok(Thermometer { cells })
+    }
+}
+
+impl Rule for Thermometer {
+    fn allows(&self, values: &[Vec<u8>], ind: [usize; 2], val: u8) -> bool {
+        let p = match self.cells.iter().position(|&cell| cell == ind) {
+            Some(p) => p,
+            None => return true,
+        };
+        // Cell `p` needs `p` smaller values below it and room above it.
+        let size = values.len();
+        let val = val as usize;
+        if val <= p || val + (self.cells.len() - 1 - p) > size {
+            return false;
+        }
+        self.cells.iter().enumerate().all(|(q, &[j, i])| {
+            let other = values[i][j] as usize;
+            match other {
+                _ if q == p => true,
+                0 => true,
+                _ if q < p => other + (p - q) <= val,
+                _ => val + (q - p) <= other,
+            }
+        })
+    }
+
+    fn marks(&self, _geometry: Geometry) -> Vec<Mark> {
+        vec![Mark::Thermometer(self.cells.clone())]
+    }
+}
+
+impl fmt::Display for Thermometer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("thermo")?;
+        write_cells(f, &self.cells)
+    }
+}
+
+/// Cells a chess knight's move apart may not hold the same value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AntiKnight;
+
+impl Rule for AntiKnight {
+    fn peers(&self, geometry: Geometry, ind: [usize; 2]) -> Vec<[usize; 2]> {
+        const MOVES: [[isize; 2]; 8] = [
+            [1, 2],
+            [2, 1],
+            [2, -1],
+            [1, -2],
+            [-1, -2],
+            [-2, -1],
+            [-2, 1],
+            [-1, 2],
+        ];
+        offsets(geometry, ind, &MOVES)
+    }
+
+    fn marks(&self, _geometry: Geometry) -> Vec<Mark> {
+        vec![Mark::Label("Anti-knight")]
+    }
+}
+
+impl fmt::Display for AntiKnight {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("anti-knight")
+    }
+}
+
+/// Cells a chess king's move apart may not hold the same value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AntiKing;
+
+impl Rule for AntiKing {
+    fn peers(&self, geometry: Geometry, ind: [usize; 2]) -> Vec<[usize; 2]> {
+        const MOVES: [[isize; 2]; 8] = [
+            [-1, -1],
+            [0, -1],
+            [1, -1],
+            [-1, 0],
+            [1, 0],
+            [-1, 1],
+            [0, 1],
+            [1, 1],
+        ];
+        offsets(geometry, ind, &MOVES)
+    }
+
+    fn marks(&self, _geometry: Geometry) -> Vec<Mark> {
+        vec![Mark::Label("Anti-king")]
+    }
+}
+
+impl fmt::Display for AntiKing {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("anti-king")
+    }
+}
+
+/// The cells `moves` away from `ind` that are on the board.
+fn offsets(
+    geometry: Geometry,
+    ind: [usize; 2],
+    moves: &[[isize; 2]],
+) -> Vec<[usize; 2]> {
+    let size = geometry.size() as isize;
+    moves
+        .iter()
+        .map(|&[dx, dy]| [ind[0] as isize + dx, ind[1] as isize + dy])
+        .filter(|&[x, y]| x >= 0 && x < size && y >= 0 && y < size)
+        .map(|[x, y]| [x as usize, y as usize])
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CLASSIC: Geometry = Geometry::CLASSIC;
+
+    #[test]
+    fn parses_and_writes_rules() {
+        let data = "# every variant\n\
+                    diagonals\n\
+                    anti-knight   # and its neighbour\n\
+                    anti-king\n\n\
+                    cage 15 r1c1 r1c2 r2c1\n\
+                    thermo r5c5 r5c6 r4c6\n";
+        let rules = Rules::parse(data, CLASSIC).unwrap();
+        assert_eq!(rules.iter().count(), 5);
+        let text = rules.to_string();
+        assert!(text.starts_with("diagonals\nanti-knight\nanti-king\n"));
+        assert!(text.contains("cage 15 r1c1 r1c2 r2c1\n"));
+        assert_eq!(Rules::parse(&text, CLASSIC), Ok(rules));
+
+        let regions = "1122\n1122\n3344\n3344".replace('\n', "");
+        let geometry = Geometry::for_size(4).unwrap();
+        let rules =
+            Rules::parse(&format!("jigsaw {}", regions), geometry).unwrap();
+        assert!(rules.replaces_boxes());
+        assert_eq!(rules.to_string(), format!("jigsaw {}\n", regions));
+    }
+
+    #[test]
+    fn reports_bad_rules() {
+        let error = |data| Rules::parse(data, CLASSIC).unwrap_err();
+        assert_eq!(error("\nspiral"), "line 2: unknown rule `spiral`");
+        assert_eq!(
+            error("diagonals 1"),
+            "line 1: `diagonals` takes no arguments"
+        );
+        assert_eq!(
+            error("cage 3 r1c1 r10c1"),
+            "line 1: cell `r10c1` is off the board"
+        );
+        assert_eq!(error("cage x r1c1"), "line 1: invalid cage sum `x`");
+        assert_eq!(error("cage 3 r1c1 r1c1"), "line 1: cell r1c1 is repeated");
+        assert_eq!(
+            error("cage 55 r1c1 r1c2 r1c3 r1c4 r1c5 r1c6 r1c7 r1c8 r1c9 r2c1"),
+            "line 1: a cage holds at most 9 cells"
+        );
+        assert_eq!(
+            error("thermo r1c1"),
+            "line 1: a thermometer needs from 2 to 9 cells"
+        );
+        let geometry = Geometry::for_size(4).unwrap();
+        let error = Rules::parse("jigsaw 1111222233334443", geometry);
+        assert_eq!(
+            error.unwrap_err(),
+            "line 1: region 3 has 5 cells instead of 4"
+        );
+    }
+
+    #[test]
+    fn checks_sums_and_order() {
+        let mut values = vec![vec![0; 9]; 9];
+        let cage = Cage::new(vec![[0, 0], [1, 0], [2, 0]], 10, 9).unwrap();
+        assert!(cage.allows(&values, [0, 0], 7));
+        assert!(!cage.allows(&values, [0, 0], 8));
+        values[0][1] = 2;
+        assert!(cage.allows(&values, [2, 0], 5));
+        assert!(!cage.allows(&values, [2, 0], 8));
+        assert!(cage.allows(&values, [4, 4], 9));
+
+        // A cage too big to exist can never be satisfied, but it must not
+        // break the arithmetic either.
+        let row = |i| (0..9).map(move |j| [j, i]);
+        let cells: Vec<_> = row(0).chain(row(1)).chain(row(2)).collect();
+        let cage = Cage {
+            cells: cells.clone(),
+            sum: 45,
+        };
+        assert!(!cage.allows(&vec![vec![0; 9]; 9], [0, 0], 1));
+        assert!(Cage::new(cells, 45, 9).is_err());
+
+        let thermo = Thermometer::new(vec![[0, 8], [1, 8], [2, 8]], 9).unwrap();
+        assert!(!thermo.allows(&values, [0, 8], 8));
+        assert!(!thermo.allows(&values, [1, 8], 1));
+        values[8][2] = 5;
+        assert!(thermo.allows(&values, [1, 8], 4));
+        assert!(!thermo.allows(&values, [1, 8], 5));
+        assert!(!thermo.allows(&values, [0, 8], 4));
+    }
+
+    #[test]
+    fn finds_peers() {
+        let rules = Rules::parse("anti-knight\ndiagonals", CLASSIC).unwrap();
+        let peers = rules.peers(CLASSIC, [0, 0]);
+        assert!(peers.contains(&[1, 2]));
+        assert!(peers.contains(&[8, 8]));
+        assert!(!peers.contains(&[0, 0]));
+        // Both knight moves from a corner stay in its box.
+        assert_eq!(peers.len(), 20 + 6);
+        let layout = rules.layout(CLASSIC);
+        assert_eq!(layout.units.len(), 29);
+        assert_eq!(layout.cell_units[4][4].len(), 5);
+        assert_eq!(layout.peers[0][0].len(), 2);
+    }
+}
@@ -0,0 +1,289 @@
+//! Saved games in progress.
+//!
+//! A save file starts with a `sudoku save VERSION` line, so that a later
+//! change to the format can tell old saves apart.
+
+use std::fmt;
+use std::fs::{read_to_string, write};
+use std::io;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::gameboard::{Assist, Cell, Gameboard, Geometry};
+use crate::history::History;
+use crate::rules::Rules;
+
+/// Version written by `SavedGame::save`.
+pub const VERSION: u32 = 1;
+
+/// A game in progress.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SavedGame {
+    /// Givens, player entries and pencil marks.
+    pub gameboard: Gameboard,
+    /// Moves that can be undone and redone.
+    pub history: History,
+    /// Time spent playing.
+    pub elapsed: Duration,
+    /// Number of wrong values entered.
+    pub mistakes: u32,
+    /// Number of hints used.
+    pub hints: u32,
+    /// How much wrong values are pointed out.
+    pub assist: Assist,
+    /// Number of mistakes ending the game, if any.
+    pub mistake_limit: Option<u32>,
+}
+
+impl SavedGame {
+    /// Reads the save file `filename`.
+    pub fn load(filename: &str) -> Result<SavedGame, String> {
+        let data = read_to_string(filename).map_err(|e| {
+            format!("failed to read save file `{}`: {}", filename, e)
+        })?;
+        data.parse()
+            .map_err(|e| format!("invalid save file `{}`: {}", filename, e))
+    }
+
+    /// Writes the game to `filename`.
+    pub fn save(&self, filename: &str) -> io::Result<()> {
+        write(filename, self.to_string())
+    }
+}
+
+/// Writes the box shape, the variant rules, the elapsed milliseconds, the mistakes and hints, the assist level and mistake limit,
+/// then one line of cells per row, then the history.
+impl fmt::Display for SavedGame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "sudoku save {}", VERSION)?;
+        writeln!(f, "geometry {}", self.gameboard.geometry)?;
+        writeln!(f, "rules")?;
+        write!(f, "{}", self.gameboard.rules)?;
+        writeln!(f, "elapsed {}", self.elapsed.as_millis())?;
+        writeln!(f, "mistakes {}", self.mistakes)?;
+        writeln!(f, "hints {}", self.hints)?;
+        writeln!(f, "assist {}", self.assist)?;
+        match self.mistake_limit {
+            Some(limit) => writeln!(f, "limit {}", limit)?,
+            None => writeln!(f, "limit none")?,
+        }
+        writeln!(f, "cells")?;
+        for row in self.gameboard.cells.iter() {
+            let cells: Vec<String> =
+                row.iter().map(|cell| cell.to_string()).collect();
+            writeln!(f, "{}", cells.join(" "))?;
+        }
+        writeln!(f, "history")?;
+        write!(f, "{}", self.history)
+    }
+}
+
+impl FromStr for SavedGame {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<SavedGame, String> {
+        let (header, body) = s.split_once('\n').unwrap_or((s, ""));
+        let version = header
+            .strip_prefix("sudoku save ")
+            .and_then(|version| version.trim().parse::<u32>().ok())
+            .ok_or("missing `sudoku save` header")?;
+        if version != VERSION {
+            return Err(format!(
+                "version {} is not supported, the latest is {}",
+                version, VERSION
+            ));
+        }
+        let (geometry, body) = parse_geometry(body)?;
+        let (rules, body) = parse_rules(body, geometry)?;
+        let (elapsed, body) = parse_count(body, "elapsed")?;
+        let (mistakes, body) = parse_count(body, "mistakes")?;
+        let (hints, body) = parse_count(body, "hints")?;
+        let (line, body) = body.split_once('\n').unwrap_or((body, ""));
+        let assist = line
+            .strip_prefix("assist ")
+            .ok_or("missing assist level")?
+            .parse()?;
+        let (line, body) = body.split_once('\n').unwrap_or((body, ""));
+        let mistake_limit = match line.strip_prefix("limit ") {
+            Some("none") => None,
+            Some(limit) => Some(limit.parse().map_err(|_| "invalid limit")?),
+            None => return Err("missing limit".into()),
+        };
+        let (gameboard, history) = parse_game(body, geometry, rules)?;
+        Ok(SavedGame {
+            gameboard,
+            history,
+            elapsed: Duration::from_millis(elapsed),
+            mistakes,
+            hints,
+            assist,
+            mistake_limit,
+        })
+    }
+}
+
+fn parse_geometry(body: &str) -> Result<(Geometry, &str), String> {
+    let (line, body) = body.split_once('\n').unwrap_or((body, ""));
+    let geometry = line
+        .strip_prefix("geometry ")
+        .ok_or("missing geometry")?
+        .parse()?;
+    Ok((geometry, body))
+}
+
+fn parse_rules(
+    body: &str,
+    geometry: Geometry,
+) -> Result<(Rules, &str), String> {
+    let body = body.strip_prefix("rules\n").ok_or("missing rules")?;
+    let end = body.find("elapsed ").ok_or("missing elapsed time")?;
+    let rules = Rules::parse(&body[..end], geometry)?;
+    Ok((rules, &body[end..]))
+}
+
+fn parse_count<'a, T: FromStr>(
+    body: &'a str,
+    name: &str,
+) -> Result<(T, &'a str), String> {
+    let (line, body) = body.split_once('\n').unwrap_or((body, ""));
+    let count = line
+        .strip_prefix(name)
+        .and_then(|count| count.strip_prefix(' '))
+        .and_then(|count| count.parse().ok())
+        .ok_or_else(|| format!("missing {}", name))?;
+    Ok((count, body))
+}
+
+/// Reads the cells and the history.
+fn parse_game(
+    body: &str,
+    geometry: Geometry,
+    rules: Rules,
+) -> Result<(Gameboard, History), String> {
+    let size = geometry.size();
+    let mut lines = body.lines();
+    if lines.next() != Some("cells") {
+        return Err("missing cells".into());
+    }
+    let mut gameboard = Gameboard::with_geometry(geometry);
+    gameboard.rules = rules;
+    for (i, row) in gameboard.cells.iter_mut().enumerate() {
+        let line = lines.next().unwrap_or_default();
+        let cells = line
+            .split_whitespace()
+            .map(str::parse)
+            .collect::<Result<Vec<Cell>, _>>()?;
+        if cells.len() != size {
+            return Err(format!("expected {} cells in row {}", size, i + 1));
+        }
+        row.copy_from_slice(&cells);
+    }
+    gameboard.update_conflicts();
+    gameboard.update_completed();
+    if lines.next() != Some("history") {
+        return Err("missing history".into());
+    }
+    let history: History = lines.collect::<Vec<_>>().join("\n").parse()?;
+    if !history.fits(size) {
+        return Err("history does not fit the board".into());
+    }
+    Ok((gameboard, history))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameboard::SIZE;
+
+    #[test]
+    fn round_trips() {
+        let mut cells = [[0; SIZE]; SIZE];
+        cells[0][0] = 5;
+        let start = Gameboard::from_cells(cells);
+        let mut gameboard = start.clone();
+        let mut history = History::new();
+        gameboard.toggle_note([2, 1], 7);
+        history.record(&start, &gameboard);
+        let before = gameboard.clone();
+        gameboard.set([8, 0], 5);
+        history.record(&before, &gameboard);
+
+        let game = SavedGame {
+            gameboard,
+            history,
+            elapsed: Duration::from_millis(83_250),
+            mistakes: 3,
+            hints: 1,
+            assist: Assist::Solution,
+            mistake_limit: Some(5),
+        };
+        let mut loaded: SavedGame = game.to_string().parse().unwrap();
+        assert_eq!(loaded, game);
+        assert!(loaded.gameboard.cells[0][8].invalid);
+        assert!(loaded.history.undo(&mut loaded.gameboard));
+        assert_eq!(loaded.gameboard, before);
+    }
+
+    #[test]
+    fn round_trips_other_sizes() {
+        let mut gameboard = Gameboard::with_geometry("2x3".parse().unwrap());
+        gameboard.set([5, 5], 6);
+        let game = SavedGame {
+            gameboard,
+            history: History::new(),
+            elapsed: Duration::ZERO,
+            mistakes: 0,
+            hints: 0,
+            assist: Assist::default(),
+            mistake_limit: None,
+        };
+        let text = game.to_string();
+        assert!(text.contains("geometry 2x3\n"));
+        assert_eq!(text.parse(), Ok(game));
+        let bad =
+            text.replace("\nhistory\n", "\nhistory\nu 6,0:0,0,0,0>1,0,0,0\n");
+        assert!(bad.parse::<SavedGame>().is_err());
+    }
+
+    #[test]
+    fn round_trips_rules() {
+        let mut gameboard = Gameboard::new();
+        let rules = "diagonals\ncage 10 r1c1 r1c2\n";
+        gameboard.rules = Rules::parse(rules, gameboard.geometry).unwrap();
+        gameboard.set([1, 1], 4);
+        let game = SavedGame {
+            gameboard,
+            history: History::new(),
+            elapsed: Duration::from_millis(20),
+            mistakes: 2,
+            hints: 5,
+            assist: Assist::None,
+            mistake_limit: Some(3),
+        };
+        let text = game.to_string();
+        assert!(text.contains(&format!("\nrules\n{}elapsed 20\n", rules)));
+        assert!(text.contains(
+            "\nelapsed 20\nmistakes 2\nhints 5\nassist none\nlimit 3\ncells\n"
+        ));
+        assert_eq!(text.parse(), Ok(game));
+        let bad = text.replace("hints 5", "hints five");
+        assert_eq!(bad.parse::<SavedGame>(), Err("missing hints".into()));
+        let bad = text.replace("assist none", "assist lots");
+        assert_eq!(
+            bad.parse::<SavedGame>(),
+            Err("unknown assist level `lots`".into())
+        );
+        let bad = text.replace("limit 3", "limit three");
+        assert_eq!(bad.parse::<SavedGame>(), Err("invalid limit".into()));
+    }
+
+    #[test]
+    fn rejects_unknown_versions() {
+        let error = "sudoku save 99\n".parse::<SavedGame>().unwrap_err();
+        assert_eq!(error, "version 99 is not supported, the latest is 1");
+        assert!("elapsed 0\n".parse::<SavedGame>().is_err());
+        assert!("sudoku save 1\nelapsed 0\ncells\n1,0,0,0\n"
+            .parse::<SavedGame>()
+            .is_err());
+    }
+}
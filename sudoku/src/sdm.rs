@@ -0,0 +1,382 @@
+//! SDM file parser.
+//!
+//! An SDM file holds a puzzle as 81 digits, row by row, with `0` or `.` for
+//! blank cells. Whitespace between cells is ignored, as is everything from a
+//! `#` to the end of the line. Other board sizes are read too, with values
+//! above 9 written as letters.
+
+use std::error::Error;
+use std::fmt;
+use std::fs::read_to_string;
+use std::io;
+
+use crate::gameboard::{char_value, Gameboard, Geometry};
+
+/// Position in an SDM file, counting lines and columns from 1.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Location {
+    /// Name of the file.
+    pub file: String,
+    /// Line number.
+    pub line: usize,
+    /// Column number, in characters.
+    pub column: usize,
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.column)
+    }
+}
+
+/// Error from loading an SDM file.
+#[derive(Debug)]
+pub enum SdmError {
+    /// The file could not be read.
+    Io {
+        /// Name of the file.
+        file: String,
+        /// Underlying error.
+        error: io::Error,
+    },
+    /// A character that is neither a cell, whitespace nor a comment.
+    UnexpectedChar {
+        /// Position of the character.
+        at: Location,
+        /// The character found.
+        found: char,
+    },
+    /// A cell after the last one of the board.
+    TooManyCells {
+        /// Position of the first extra cell.
+        at: Location,
+        /// Number of cells on the board.
+        expected: usize,
+    },
+    /// The file ended before the board was full.
+    TooFewCells {
+        /// Position of the end of the file.
+        at: Location,
+        /// Number of cells on the board.
+        expected: usize,
+        /// Number of cells found.
+        found: usize,
+    },
+}
+
+impl fmt::Display for SdmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SdmError::Io { file, error } => {
+                write!(f, "failed to read SDM file `{}`: {}", file, error)
+            }
+            SdmError::UnexpectedChar { at, found } => {
+                write!(f, "{}: unexpected character `{}`", at, found)
+            }
+            SdmError::TooManyCells { at, expected } => {
+                write!(f, "{}: more than {} cells", at, expected)
+            }
+            SdmError::TooFewCells {
+                at,
+                expected,
+                found,
+            } => write!(
+                f,
+                "{}: expected {} cells, found {}",
+                at, expected, found
+            ),
+        }
+    }
+}
+
+impl Error for SdmError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SdmError::Io { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// Reads the SDM file in `filename`.
+pub fn load(filename: &str) -> Result<Gameboard, SdmError> {
+    let data = read_to_string(filename).map_err(|error| SdmError::Io {
+        file: filename.to_string(),
+        error,
+    })?;
+    parse(&data, filename)
+}
+
+/// Parses SDM `data`, naming `file` in errors.
+pub fn parse(data: &str, file: &str) -> Result<Gameboard, SdmError> {
+    let mut puzzles = parse_grids(data, file, "", false, 1)?;
+    Ok(puzzles.remove(0))
+}
+
+/// Parses a collection of SDM puzzles, usually one per line.
+pub fn parse_all(data: &str, file: &str) -> Result<Vec<Gameboard>, SdmError> {
+    parse_grids(data, file, "", false, usize::MAX)
+}
+
+/// Parses up to `max` puzzles written cell by cell, skipping whitespace,
+/// comments and the characters in `separators`.
+///
+/// If `rows` is set, the first line holding cells is a row of a board and
+/// gives its size. Otherwise the size comes from the layout of the lines up
+/// to the first blank line, see `free_layout`. A new puzzle starts once the previous one has all its
+/// cells, wherever the line breaks are.
+pub(crate) fn parse_grids(
+    data: &str,
+    file: &str,
+    separators: &str,
+    rows: bool,
+    max: usize,
+) -> Result<Vec<Gameboard>, SdmError> {
+    let at = |line: usize, column: usize| Location {
+        file: file.to_string(),
+        line,
+        column,
+    };
+    let is_cell = |c: char| !c.is_whitespace() && !separators.contains(c);
+    let cells_in = |line: &str| {
+        strip_comment(line).chars().filter(|&c| is_cell(c)).count()
+    };
+    // The lines of the first puzzle, up to a blank line, give the layout.
+    let counts: Vec<usize> = data
+        .lines()
+        .skip_while(|line| cells_in(line) == 0)
+        .take_while(|line| !line.trim().is_empty())
+        .map(cells_in)
+        .filter(|&count| count > 0)
+        .collect();
+    let geometry = if rows {
+        counts
+            .first()
+            .and_then(|&first| Geometry::for_size(first))
+            .unwrap_or(Geometry::CLASSIC)
+    } else {
+        free_layout(&counts)
+    };
+    let size = geometry.size();
+
+    let mut puzzles = Vec::new();
+    let mut cells = vec![vec![0; size]; size];
+    let mut count = 0;
+    let mut end = at(1, 1);
+    for (i, line) in data.lines().enumerate() {
+        let line = strip_comment(line);
+        for (j, c) in line.chars().enumerate() {
+            if !is_cell(c) {
+                continue;
+            }
+            let value = match c {
+                '.' | '0' => 0,
+                _ => match char_value(c) {
+                    Some(value) if value as usize <= size => value,
+                    _ => {
+                        return Err(SdmError::UnexpectedChar {
+                            at: at(i + 1, j + 1),
+                            found: c,
+                        })
+                    }
+                },
+            };
+            if count == size * size {
+                if puzzles.len() + 1 == max {
+                    return Err(SdmError::TooManyCells {
+                        at: at(i + 1, j + 1),
+                        expected: size * size,
+                    });
+                }
+                puzzles.push(Gameboard::from_values(geometry, &cells));
+                count = 0;
+            }
+            cells[count / size][count % size] = value;
+            count += 1;
+        }
+        end = at(i + 1, line.chars().count() + 1);
+    }
+    if count < size * size {
+        return Err(SdmError::TooFewCells {
+            at: end,
+            expected: size * size,
+            found: count,
+        });
+    }
+    puzzles.push(Gameboard::from_values(geometry, &cells));
+    Ok(puzzles)
+}
+
+/// The geometry of boards written in a free layout, from the number of cells
+/// on each line holding any.
+///
+/// At least `n` leading lines of `n` cells are a board laid out one row per
+/// line, and several leading lines of `n * n` cells a collection of boards
+/// one per line. Otherwise the boards are classic, unless the cells are more
+/// than a classic board holds and fill exactly one larger board, so that a
+/// truncated classic board is reported as such rather than read as a
+/// smaller board.
+fn free_layout(counts: &[usize]) -> Geometry {
+    let classic = Geometry::CLASSIC.size() * Geometry::CLASSIC.size();
+    let side = |cells: usize| {
+        (2..=cells)
+            .find(|k| k * k == cells)
+            .and_then(Geometry::for_size)
+    };
+    let first = counts.first().copied().unwrap_or(0);
+    let run = counts.iter().take_while(|&&count| count == first).count();
+    let stated = if run >= first {
+        Geometry::for_size(first)
+    } else if run > 1 {
+        side(first)
+    } else {
+        None
+    };
+    let total: usize = counts.iter().sum();
+    stated
+        .or_else(|| Some(total).filter(|&n| n > classic).and_then(side))
+        .unwrap_or(Geometry::CLASSIC)
+}
+
+/// Cuts a `#` comment off `line`.
+fn strip_comment(line: &str) -> &str {
+    line.split('#').next().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PUZZLE: &str = "016400000200009000400000062070230100100000003\
+                          003087040960000005000800007000006820";
+
+    #[test]
+    fn accepts_layout_and_comments() {
+        let want = parse(PUZZLE, "a.sdm").unwrap();
+        let grid = "# Example puzzle\n\
+                    .164..... 2....9...\n\
+                    4......62 .7.23.1..   # row 4\n\
+                    1.......3\t..3.87.4.\n\
+                    96......5\n\
+                    ...8....7\n\
+                    .....682.\n\n";
+        assert_eq!(parse(grid, "b.sdm").unwrap(), want);
+        assert_eq!(parse(&format!("{}\r\n", PUZZLE), "c.sdm").unwrap(), want);
+    }
+
+    #[test]
+    fn reports_unexpected_char() {
+        let data = format!("# comment\n{}x", &PUZZLE[..40]);
+        let error = parse(&data, "bad.sdm").unwrap_err();
+        match error {
+            SdmError::UnexpectedChar { ref at, found } => {
+                assert_eq!((at.line, at.column, found), (2, 41, 'x'));
+            }
+            _ => panic!("unexpected error {:?}", error),
+        }
+        assert_eq!(error.to_string(), "bad.sdm:2:41: unexpected character `x`");
+    }
+
+    #[test]
+    fn reports_cell_count() {
+        let error = parse(&format!("{}\n1", PUZZLE), "long.sdm").unwrap_err();
+        assert_eq!(error.to_string(), "long.sdm:2:1: more than 81 cells");
+
+        let error = parse(&PUZZLE[..80], "short.sdm").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "short.sdm:1:81: expected 81 cells, found 80"
+        );
+        assert!(parse("", "empty.sdm").is_err());
+    }
+
+    #[test]
+    fn sizes_free_layout_by_cell_count() {
+        let want = parse(PUZZLE, "a.sdm").unwrap();
+        for &first in &[16, 36] {
+            let grid = format!("{}\n{}\n", &PUZZLE[..first], &PUZZLE[first..]);
+            assert_eq!(parse(&grid, "free.sdm").unwrap(), want);
+            let puzzles = parse_all(&grid, "free.sdm").unwrap();
+            assert_eq!(puzzles.len(), 1);
+            assert_eq!(puzzles[0], want);
+        }
+    }
+
+    #[test]
+    fn reads_truncated_boards_as_classic() {
+        for &cells in &[16, 36] {
+            let error = parse(&PUZZLE[..cells], "short.sdm").unwrap_err();
+            assert_eq!(
+                error.to_string(),
+                format!(
+                    "short.sdm:1:{}: expected 81 cells, found {}",
+                    cells + 1,
+                    cells
+                )
+            );
+        }
+
+        // Laid out one row per line, 36 cells are a 6x6 board.
+        let puzzle = parse(&"1.....\n".repeat(6), "6.sdm").unwrap();
+        assert_eq!(puzzle.size(), 6);
+    }
+
+    #[test]
+    fn parses_collections() {
+        let data = format!("# two puzzles\n{}\n\n{}\n", PUZZLE, PUZZLE);
+        let puzzles = parse_all(&data, "many.sdm").unwrap();
+        assert_eq!(puzzles.len(), 2);
+        assert_eq!(puzzles[1], parse(PUZZLE, "a.sdm").unwrap());
+
+        let data = format!("{}\n{}", PUZZLE, &PUZZLE[..9]);
+        let error = parse_all(&data, "many.sdm").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "many.sdm:2:10: expected 81 cells, found 9"
+        );
+    }
+
+    #[test]
+    fn parses_other_sizes() {
+        let puzzles =
+            parse_all("1234341221434321\n0..4.....1......\n", "4.sdm").unwrap();
+        assert_eq!(puzzles.len(), 2);
+        assert_eq!(puzzles[1].size(), 4);
+        assert_eq!(puzzles[1].cells[0][3].value, 4);
+
+        let mut data = String::from("G");
+        data.push_str(&".".repeat(255));
+        let puzzle = parse(&data, "16.sdm").unwrap();
+        assert_eq!(puzzle.geometry, Geometry::for_size(16).unwrap());
+        assert_eq!(puzzle.cells[0][0].value, 16);
+        assert_eq!(puzzle.to_sdm(), data.replace('.', "0"));
+
+        // A board laid out one row per line is one puzzle, not many.
+        let rows: Vec<&str> = (0..16).map(|i| &data[i * 16..][..16]).collect();
+        let rows = rows.join("\n");
+        assert_eq!(parse(&rows, "16.sdm").unwrap(), puzzle);
+
+        // So is every board of a collection, with or without blank lines
+        // between them, whatever the layout of the later ones.
+        for &second in &[&rows, &data] {
+            for &gap in &["\n", "\n\n"] {
+                let boards = format!("{}{}{}\n", rows, gap, second);
+                let puzzles = parse_all(&boards, "16.sdm").unwrap();
+                assert_eq!(puzzles, [puzzle.clone(), puzzle.clone()]);
+            }
+        }
+
+        let error = parse("H...............", "4.sdm").unwrap_err();
+        assert_eq!(error.to_string(), "4.sdm:1:1: unexpected character `H`");
+    }
+
+    #[test]
+    fn reports_missing_file() {
+        match load("static/missing.sdm") {
+            Err(SdmError::Io { file, .. }) => {
+                assert_eq!(file, "static/missing.sdm")
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,420 @@
+//! Backtracking Sudoku solver.
+
+use crate::gameboard::{Cell, Gameboard, Geometry};
+use crate::rules::{Layout, Rules};
+
+/// Solves the puzzle given by the loaded cells of `gameboard`.
+///
+/// Only cells with `Cell::loaded` set are treated as givens; values entered by
+/// the player are ignored. Returns the completed board, or `None` if the
+/// givens contradict each other or admit no solution.
+pub fn solve(gameboard: &Gameboard) -> Option<Gameboard> {
+    let mut solver = Solver::new(gameboard)?;
+    if !solver.search() {
+        return None;
+    }
+    let mut solved = Gameboard::with_geometry(gameboard.geometry);
+    solved.rules = gameboard.rules.clone();
+    for (i, row) in solved.cells.iter_mut().enumerate() {
+        for (j, cell) in row.iter_mut().enumerate() {
+            *cell = Cell {
+                value: solver.grid[i][j],
+                loaded: gameboard.cells[i][j].loaded,
+                ..Cell::default()
+            };
+        }
+    }
+    solved.completed = true;
+    Some(solved)
+}
+
+/// Counts the solutions of the puzzle given by the loaded cells of
+/// `gameboard`, stopping once `limit` solutions have been found.
+pub fn count_solutions(gameboard: &Gameboard, limit: usize) -> usize {
+    match Solver::new(gameboard) {
+        Some(mut solver) if limit > 0 => solver.count(limit),
+        _ => 0,
+    }
+}
+
+/// Whether a puzzle has exactly one solution.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Uniqueness {
+    /// The givens admit no solution.
+    Unsolvable,
+    /// The puzzle is well-posed.
+    Unique,
+    /// The givens admit more than one solution.
+    Ambiguous,
+}
+
+/// Checks that the puzzle given by `gameboard` has exactly one solution.
+pub fn uniqueness(gameboard: &Gameboard) -> Uniqueness {
+    match count_solutions(gameboard, 2) {
+        0 => Uniqueness::Unsolvable,
+        1 => Uniqueness::Unique,
+        _ => Uniqueness::Ambiguous,
+    }
+}
+
+/// Search state, tracking the values used in each unit.
+struct Solver {
+    geometry: Geometry,
+    rules: Rules,
+    grid: Vec<Vec<u8>>,
+    layout: Layout,
+    /// Values placed in each unit.
+    used: Vec<u32>,
+}
+
+impl Solver {
+    /// Creates a solver from the givens of `gameboard`, or `None` if they
+    /// break the rules.
+    fn new(gameboard: &Gameboard) -> Option<Solver> {
+        let geometry = gameboard.geometry;
+        let size = geometry.size();
+        let rules = gameboard.rules.clone();
+        let layout = rules.layout(geometry);
+        let mut solver = Solver {
+            geometry,
+            rules,
+            grid: vec![vec![0; size]; size],
+            used: vec![0; layout.units.len()],
+            layout,
+        };
+        for (i, row) in gameboard.cells.iter().enumerate() {
+            for (j, cell) in row.iter().enumerate() {
+                if !cell.loaded || cell.value == 0 {
+                    continue;
+                }
+                if solver.candidates(i, j) & (1 << cell.value) == 0 {
+                    return None;
+                }
+                solver.place(i, j, cell.value);
+            }
+        }
+        Some(solver)
+    }
+
+    /// Values that may still be placed at `row` and `col`.
+    fn candidates(&self, row: usize, col: usize) -> u32 {
+        let mut used = 0;
+        for &n in &self.layout.cell_units[row][col] {
+            used |= self.used[n];
+        }
+        for &[j, i] in &self.layout.peers[row][col] {
+            used |= 1 << self.grid[i][j];
+        }
+        let mut candidates = !used & self.geometry.all_values();
+        if !self.rules.is_empty() {
+            for val in values(candidates) {
+                if !self.rules.allows(&self.grid, [col, row], val) {
+                    candidates &= !(1 << val);
+                }
+            }
+        }
+        candidates
+    }
+
+    fn place(&mut self, row: usize, col: usize, val: u8) {
+        self.grid[row][col] = val;
+        for &n in &self.layout.cell_units[row][col] {
+            self.used[n] |= 1 << val;
+        }
+    }
+
+    fn remove(&mut self, row: usize, col: usize, val: u8) {
+        self.grid[row][col] = 0;
+        for &n in &self.layout.cell_units[row][col] {
+            self.used[n] &= !(1 << val);
+        }
+    }
+
+    /// Finds the empty cell with the fewest candidates.
+    ///
+    /// A value that fits in only one cell of a row, column or box is placed
+    /// first, by returning that cell with the value as its only candidate.
+    /// Returns `None` if the grid is full, and a cell with no candidates if
+    /// the current state is a dead end.
+    fn most_constrained(&self) -> Option<(usize, usize, u32)> {
+        let size = self.geometry.size();
+        let mut best = None;
+        let mut best_count = u32::MAX;
+        for i in 0..size {
+            for j in 0..size {
+                if self.grid[i][j] != 0 {
+                    continue;
+                }
+                let candidates = self.candidates(i, j);
+                let count = candidates.count_ones();
+                if count < best_count {
+                    best = Some((i, j, candidates));
+                    best_count = count;
+                    if count <= 1 {
+                        return best;
+                    }
+                }
+            }
+        }
+        best?;
+        self.hidden_single().or(best)
+    }
+
+    /// Finds a value with only one place left in some unit holding every
+    /// value, such as a row, column or box.
+    ///
+    /// Returns the cell with that value as its only candidate, or an empty
+    /// cell with no candidates if a value has no place left at all.
+    fn hidden_single(&self) -> Option<(usize, usize, u32)> {
+        let size = self.geometry.size();
+        for unit in self.layout.units.iter().filter(|unit| unit.len() == size) {
+            // Values with a place in the unit, and with two or more.
+            let (mut once, mut twice, mut placed) = (0u32, 0u32, 0u32);
+            for &[j, i] in unit {
+                match self.grid[i][j] {
+                    0 => {
+                        let candidates = self.candidates(i, j);
+                        twice |= once & candidates;
+                        once |= candidates;
+                    }
+                    val => placed |= 1 << val,
+                }
+            }
+            let missing = self.geometry.all_values() & !placed;
+            let single = missing & !twice;
+            if single == 0 {
+                continue;
+            }
+            let bit = single & single.wrapping_neg();
+            let candidates = if missing & !once != 0 { 0 } else { bit };
+            let &[j, i] = unit.iter().find(|&&[j, i]| {
+                self.grid[i][j] == 0
+                    && (candidates == 0 || self.candidates(i, j) & bit != 0)
+            })?;
+            return Some((i, j, candidates));
+        }
+        None
+    }
+
+    /// Fills the remaining empty cells, returning whether it succeeded.
+    fn search(&mut self) -> bool {
+        let (row, col, candidates) = match self.most_constrained() {
+            Some(cell) => cell,
+            None => return true,
+        };
+        for val in values(candidates) {
+            self.place(row, col, val);
+            if self.search() {
+                return true;
+            }
+            self.remove(row, col, val);
+        }
+        false
+    }
+
+    /// Counts the ways to fill the remaining empty cells, up to `limit`.
+    fn count(&mut self, limit: usize) -> usize {
+        let (row, col, candidates) = match self.most_constrained() {
+            Some(cell) => cell,
+            None => return 1,
+        };
+        let mut total = 0;
+        for val in values(candidates) {
+            self.place(row, col, val);
+            total += self.count(limit - total);
+            self.remove(row, col, val);
+            if total >= limit {
+                break;
+            }
+        }
+        total
+    }
+}
+
+/// Iterates over the values in `mask`, from lowest to highest.
+fn values(mask: u32) -> impl Iterator<Item = u8> {
+    (1..32u8).filter(move |&val| mask & (1 << val) != 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::corpus::{board, puzzle, CORPUS};
+    use crate::gameboard::SIZE;
+    use crate::Difficulty;
+
+    fn assert_solves(puzzle: &Gameboard, solved: &Gameboard) {
+        for i in 0..puzzle.size() {
+            for j in 0..puzzle.size() {
+                let given = puzzle.cells[i][j];
+                let cell = solved.cells[i][j];
+                assert_ne!(cell.value, 0);
+                assert_eq!(cell.loaded, given.loaded);
+                if given.loaded {
+                    assert_eq!(cell.value, given.value);
+                }
+            }
+        }
+        let geometry = puzzle.geometry;
+        let size = geometry.size();
+        let all = geometry.all_values();
+        for k in 0..size {
+            let (mut row, mut col, mut sec) = (0u32, 0u32, 0u32);
+            for m in 0..size {
+                let [j, i] = geometry.box_cell(k, m);
+                row |= 1 << solved.cells[k][m].value;
+                col |= 1 << solved.cells[m][k].value;
+                sec |= 1 << solved.cells[i][j].value;
+            }
+            assert_eq!((row, col, sec), (all, all, all));
+        }
+        assert!(solved.completed);
+    }
+
+    #[test]
+    fn solves_corpus() {
+        for &(puzzle, _) in CORPUS {
+            let puzzle = board(puzzle);
+            let solved = solve(&puzzle).expect("puzzle has a solution");
+            assert_solves(&puzzle, &solved);
+        }
+    }
+
+    #[test]
+    fn solves_empty_board() {
+        let puzzle = Gameboard::new();
+        let solved = solve(&puzzle).expect("empty board has a solution");
+        assert_solves(&puzzle, &solved);
+    }
+
+    #[test]
+    fn solves_other_sizes() {
+        for &shape in &["2x2", "3x2", "2x3", "4x3", "4x4", "5x5"] {
+            let puzzle = Gameboard::with_geometry(shape.parse().unwrap());
+            let solved = solve(&puzzle).expect("empty board has a solution");
+            assert_solves(&puzzle, &solved);
+        }
+        let puzzle = Gameboard::from_cells([
+            [0, 0, 3, 0, 1, 0],
+            [5, 6, 0, 3, 2, 0],
+            [0, 5, 4, 2, 0, 3],
+            [2, 0, 6, 4, 5, 0],
+            [0, 1, 2, 0, 4, 5],
+            [0, 4, 0, 1, 0, 0],
+        ]);
+        assert_eq!(uniqueness(&puzzle), Uniqueness::Unique);
+        assert_solves(&puzzle, &solve(&puzzle).unwrap());
+    }
+
+    #[test]
+    fn solves_variants() {
+        // Some givens keep the search from wandering, as the leftover cells
+        // of the first band of regions must match.
+        let jigsaw = "jigsaw 111122333111222333112222333\
+                      444555666444555666444555666\
+                      777888999777888999777888999";
+        let variants = [
+            ("diagonals", ""),
+            ("anti-knight", ""),
+            ("anti-king", ""),
+            ("cage 6 r1c1 r1c2 r1c3\nthermo r9c1 r9c2 r9c3 r9c4 r8c4", ""),
+            (
+                jigsaw,
+                "1..2..9..97.8..5.28..9..3..7..42.1..4.15..7..\
+                 5..7..24.6..1.24..2..3..6..34.6..8.1",
+            ),
+        ];
+        for &(variant, givens) in &variants {
+            let mut puzzle = board(givens);
+            puzzle.rules = Rules::parse(variant, puzzle.geometry).unwrap();
+            let solved = solve(&puzzle).expect("variant has a solution");
+            let values = solved.values();
+            for unit in puzzle.rules.units(puzzle.geometry) {
+                let mut seen = 0u32;
+                for [j, i] in unit {
+                    assert_eq!(seen & 1 << values[i][j], 0, "{}", variant);
+                    seen |= 1 << values[i][j];
+                }
+            }
+            for i in 0..SIZE {
+                for j in 0..SIZE {
+                    let val = values[i][j];
+                    for [b, a] in solved.peers([j, i]) {
+                        assert_ne!(values[a][b], val, "{}", variant);
+                    }
+                    assert!(solved.rules.allows(&values, [j, i], val));
+                }
+            }
+        }
+
+        let mut puzzle = puzzle(Difficulty::Hard);
+        puzzle.rules =
+            Rules::parse("cage 3 r1c1 r2c1", puzzle.geometry).unwrap();
+        assert_eq!(uniqueness(&puzzle), Uniqueness::Unsolvable);
+    }
+
+    #[test]
+    fn ignores_player_entries() {
+        let mut puzzle = puzzle(Difficulty::Hard);
+        // 1 is already in this row, so the entry can never be right.
+        puzzle.set([0, 0], 1);
+        let solved = solve(&puzzle).expect("puzzle has a solution");
+        assert_eq!(solved.cells[0][0].value, 3);
+    }
+
+    #[test]
+    fn corpus_is_well_posed() {
+        for &(puzzle, _) in CORPUS {
+            assert_eq!(uniqueness(&board(puzzle)), Uniqueness::Unique);
+        }
+    }
+
+    #[test]
+    fn counts_up_to_limit() {
+        let empty = Gameboard::new();
+        assert_eq!(count_solutions(&empty, 0), 0);
+        assert_eq!(count_solutions(&empty, 1), 1);
+        assert_eq!(count_solutions(&empty, 50), 50);
+        assert_eq!(uniqueness(&empty), Uniqueness::Ambiguous);
+    }
+
+    #[test]
+    fn detects_ambiguous_puzzle() {
+        // The 8 and 6 in columns 1 and 6 of the top two rows can swap.
+        let mut puzzle = solve(&puzzle(Difficulty::Easy)).unwrap();
+        for row in puzzle.cells.iter_mut() {
+            for cell in row.iter_mut() {
+                cell.loaded = true;
+            }
+        }
+        assert_eq!(count_solutions(&puzzle, 5), 1);
+        for [j, i] in [[1, 0], [6, 0], [1, 1], [6, 1]] {
+            puzzle.cells[i][j].loaded = false;
+        }
+        assert_eq!(count_solutions(&puzzle, 5), 2);
+    }
+
+    #[test]
+    fn rejects_conflicting_givens() {
+        let mut cells = [[0; SIZE]; SIZE];
+        cells[0][0] = 5;
+        cells[4][4] = 5;
+        cells[1][1] = 5;
+        let puzzle = Gameboard::from_cells(cells);
+        assert_eq!(solve(&puzzle), None);
+        assert_eq!(uniqueness(&puzzle), Uniqueness::Unsolvable);
+    }
+
+    #[test]
+    fn rejects_unsolvable_givens() {
+        // The top left cell can only hold 9, which its column already has.
+        let mut cells = [[0; SIZE]; SIZE];
+        for (j, cell) in cells[0].iter_mut().enumerate().skip(1) {
+            *cell = j as u8;
+        }
+        cells[8][0] = 9;
+        let puzzle = Gameboard::from_cells(cells);
+        assert_eq!(solve(&puzzle), None);
+        assert_eq!(count_solutions(&puzzle, 2), 0);
+    }
+}
@@ -0,0 +1,490 @@
+//! Statistics of finished games.
+//!
+//! Every completed puzzle is recorded with its time, mistakes and hints in a
+//! JSON file, from which the best times per difficulty are worked out:
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "games": [
+//!     {"puzzle": "0164...", "difficulty": "easy", "millis": 312500,
+//!      "mistakes": 2, "hints": 1}
+//!   ]
+//! }
+//! ```
+
+use std::fmt;
+use std::fs::{read_to_string, write};
+use std::io::{self, ErrorKind};
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::generator::Difficulty;
+
+/// Version written by `Stats::save`.
+pub const VERSION: u32 = 1;
+
+/// How one puzzle was played.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GameStats {
+    /// The givens of the puzzle as an SDM line.
+    pub puzzle: String,
+    /// Difficulty of the puzzle.
+    pub difficulty: Difficulty,
+    /// Time taken to complete the puzzle.
+    pub elapsed: Duration,
+    /// Number of wrong values entered: values clashing with another cell, or
+    /// differing from the solution when that was checked.
+    pub mistakes: u32,
+    /// Number of hints used.
+    pub hints: u32,
+}
+
+/// All games played to the end.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Stats {
+    /// Finished games, oldest first.
+    pub games: Vec<GameStats>,
+}
+
+impl Stats {
+    /// Reads the statistics file `filename`, starting afresh if there is
+    /// none yet.
+    pub fn load(filename: &str) -> Result<Stats, String> {
+        let data = match read_to_string(filename) {
+            Ok(data) => data,
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                return Ok(Stats::default())
+            }
+            Err(e) => {
+                return Err(format!(
+                    "failed to read statistics file `{}`: {}",
+                    filename, e
+                ))
+            }
+        };
+        data.parse().map_err(|e| {
+            format!("invalid statistics file `{}`: {}", filename, e)
+        })
+    }
+
+    /// Writes the statistics to `filename`.
+    pub fn save(&self, filename: &str) -> io::Result<()> {
+        write(filename, self.to_string())
+    }
+
+    /// Adds a finished game.
+    pub fn record(&mut self, game: GameStats) {
+        self.games.push(game);
+    }
+
+    /// Games of `difficulty`.
+    pub fn games_of(
+        &self,
+        difficulty: Difficulty,
+    ) -> impl Iterator<Item = &GameStats> {
+        self.games
+            .iter()
+            .filter(move |game| game.difficulty == difficulty)
+    }
+
+    /// Fastest time for a puzzle of `difficulty`.
+    pub fn best(&self, difficulty: Difficulty) -> Option<Duration> {
+        self.games_of(difficulty).map(|game| game.elapsed).min()
+    }
+
+    /// Average time for a puzzle of `difficulty`.
+    pub fn average(&self, difficulty: Difficulty) -> Option<Duration> {
+        let count = self.games_of(difficulty).count() as u32;
+        let total: Duration =
+            self.games_of(difficulty).map(|game| game.elapsed).sum();
+        if count == 0 {
+            return None;
+        }
+        Some(total / count)
+    }
+}
+
+/// Writes the statistics as JSON, one game per line.
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{{")?;
+        writeln!(f, "  \"version\": {},", VERSION)?;
+        write!(f, "  \"games\": [")?;
+        for (k, game) in self.games.iter().enumerate() {
+            let separator = if k == 0 { "" } else { "," };
+            write!(
+                f,
+                "{}\n    {{\"puzzle\": {}, \"difficulty\": \"{}\", \
+                 \"millis\": {}, \"mistakes\": {}, \"hints\": {}}}",
+                separator,
+                quote(&game.puzzle),
+                game.difficulty,
+                game.elapsed.as_millis(),
+                game.mistakes,
+                game.hints
+            )?;
+        }
+        if !self.games.is_empty() {
+            write!(f, "\n  ")?;
+        }
+        writeln!(f, "]")?;
+        writeln!(f, "}}")
+    }
+}
+
+impl FromStr for Stats {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Stats, String> {
+        let json = Json::parse(s)?;
+        match json.get("version").and_then(Json::as_u64) {
+            Some(1) => {}
+            Some(version) => {
+                return Err(format!(
+                    "version {} is not supported, the latest is {}",
+                    version, VERSION
+                ))
+            }
+            None => return Err("missing version".into()),
+        }
+        let games = match json.get("games") {
+            Some(Json::Array(games)) => games,
+            _ => return Err("missing games".into()),
+        };
+        let games = games
+            .iter()
+            .enumerate()
+            .map(|(k, game)| {
+                parse_game(game).map_err(|e| format!("game {}: {}", k + 1, e))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Stats { games })
+    }
+}
+
+fn parse_game(game: &Json) -> Result<GameStats, String> {
+    let field =
+        |name: &str| game.get(name).ok_or_else(|| format!("missing {}", name));
+    let number = |name: &str| {
+        field(name)?
+            .as_u64()
+            .ok_or_else(|| format!("{} is not a whole number", name))
+    };
+    let text = |name: &str| match field(name)? {
+        Json::String(text) => Ok(text.clone()),
+        _ => Err(format!("{} is not a string", name)),
+    };
+    Ok(GameStats {
+        puzzle: text("puzzle")?,
+        difficulty: text("difficulty")?.parse()?,
+        elapsed: Duration::from_millis(number("millis")?),
+        mistakes: number("mistakes")? as u32,
+        hints: number("hints")? as u32,
+    })
+}
+
+/// Formats `duration` as minutes and seconds, with hours once there are any.
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    let (hours, minutes) = (seconds / 3600, seconds / 60 % 60);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, seconds % 60)
+    } else {
+        format!("{}:{:02}", minutes, seconds % 60)
+    }
+}
+
+/// A JSON value.
+#[derive(Clone, Debug, PartialEq)]
+enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    /// Parses a whole JSON document.
+    fn parse(s: &str) -> Result<Json, String> {
+        let mut parser = Parser { s, pos: 0 };
+        let value = parser.value()?;
+        parser.skip_whitespace();
+        if parser.pos < s.len() {
+            return Err(parser.error());
+        }
+        Ok(value)
+    }
+
+    /// The member `name` of an object.
+    fn get(&self, name: &str) -> Option<&Json> {
+        match self {
+            Json::Object(members) => members
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    fn as_u64(&self) -> Option<u64> {
+        match *self {
+            Json::Number(n) if n >= 0.0 && n.fract() == 0.0 => Some(n as u64),
+            _ => None,
+        }
+    }
+}
+
+/// Writes `s` as a JSON string.
+pub(crate) fn quote(s: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            c if (c as u32) < 0x20 => {
+                quoted.push_str(&format!("\\u{:04x}", c as u32))
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Recursive descent parser over the bytes of a JSON document.
+struct Parser<'a> {
+    s: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self) -> String {
+        format!("invalid JSON at byte {}", self.pos + 1)
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.s.as_bytes().get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ') | Some(b'\t') | Some(b'\n') | Some(b'\r') =
+            self.peek()
+        {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, token: &str) -> Result<(), String> {
+        self.skip_whitespace();
+        if self.s[self.pos..].starts_with(token) {
+            self.pos += token.len();
+            Ok(())
+        } else {
+            Err(self.error())
+        }
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(b'{') => self.object(),
+            Some(b'[') => self.array(),
+            Some(b'"') => self.string().map(Json::String),
+            Some(b't') => self.expect("true").map(|_| Json::Bool(true)),
+            Some(b'f') => self.expect("false").map(|_| Json::Bool(false)),
+            Some(b'n') => self.expect("null").map(|_| Json::Null),
+            _ => self.number(),
+        }
+    }
+
+    fn object(&mut self) -> Result<Json, String> {
+        self.expect("{")?;
+        let mut members = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(b'}') {
+            self.pos += 1;
+            return Ok(Json::Object(members));
+        }
+        loop {
+            self.skip_whitespace();
+            let key = self.string()?;
+            self.expect(":")?;
+            members.push((key, self.value()?));
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b'}') => {
+                    self.pos += 1;
+                    return Ok(Json::Object(members));
+                }
+                _ => return Err(self.error()),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Json, String> {
+        self.expect("[")?;
+        let mut items = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(b']') {
+            self.pos += 1;
+            return Ok(Json::Array(items));
+        }
+        loop {
+            items.push(self.value()?);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b']') => {
+                    self.pos += 1;
+                    return Ok(Json::Array(items));
+                }
+                _ => return Err(self.error()),
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        if self.peek() != Some(b'"') {
+            return Err(self.error());
+        }
+        self.pos += 1;
+        let mut s = String::new();
+        let mut chars = self.s[self.pos..].char_indices();
+        while let Some((k, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.pos += k + 1;
+                    return Ok(s);
+                }
+                '\\' => {
+                    let escaped = match chars.next().map(|(_, c)| c) {
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('/') => '/',
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('u') => {
+                            let hex: String = chars
+                                .by_ref()
+                                .take(4)
+                                .map(|(_, c)| c)
+                                .collect();
+                            u32::from_str_radix(&hex, 16)
+                                .ok()
+                                .and_then(std::char::from_u32)
+                                .unwrap_or('\u{fffd}')
+                        }
+                        _ => {
+                            self.pos += k;
+                            return Err(self.error());
+                        }
+                    };
+                    s.push(escaped);
+                }
+                c => s.push(c),
+            }
+        }
+        self.pos = self.s.len();
+        Err(self.error())
+    }
+
+    fn number(&mut self) -> Result<Json, String> {
+        let start = self.pos;
+        while let Some(b'0'..=b'9') | Some(b'-') | Some(b'+') | Some(b'.')
+        | Some(b'e') | Some(b'E') = self.peek()
+        {
+            self.pos += 1;
+        }
+        self.s[start..self.pos]
+            .parse()
+            .map(Json::Number)
+            .map_err(|_| {
+                self.pos = start;
+                self.error()
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn game(difficulty: Difficulty, secs: u64) -> GameStats {
+        GameStats {
+            puzzle: "1.3".repeat(27),
+            difficulty,
+            elapsed: Duration::from_secs(secs),
+            mistakes: 2,
+            hints: 1,
+        }
+    }
+
+    #[test]
+    fn round_trips() {
+        let mut stats = Stats::default();
+        assert_eq!(stats.to_string().parse(), Ok(stats.clone()));
+        stats.record(game(Difficulty::Easy, 300));
+        stats.record(game(Difficulty::Hard, 900));
+        stats.record(game(Difficulty::Easy, 200));
+        let text = stats.to_string();
+        assert!(text.contains("\"difficulty\": \"hard\", \"millis\": 900000"));
+        assert_eq!(text.parse(), Ok(stats));
+    }
+
+    #[test]
+    fn finds_best_times() {
+        let mut stats = Stats::default();
+        stats.record(game(Difficulty::Easy, 300));
+        stats.record(game(Difficulty::Easy, 200));
+        stats.record(game(Difficulty::Medium, 500));
+        let secs = |d: Option<Duration>| d.map(|d| d.as_secs());
+        assert_eq!(secs(stats.best(Difficulty::Easy)), Some(200));
+        assert_eq!(secs(stats.average(Difficulty::Easy)), Some(250));
+        assert_eq!(secs(stats.best(Difficulty::Medium)), Some(500));
+        assert_eq!(stats.best(Difficulty::Expert), None);
+        assert_eq!(stats.average(Difficulty::Expert), None);
+    }
+
+    #[test]
+    fn reads_any_json_layout() {
+        let data = r#" { "games" : [ { "hints":0, "mistakes":1,
+            "millis":61000, "difficulty":"medium", "puzzle":"a\"bA",
+            "note": [true, null, -1.5e3, {}] } ], "version":1 } "#;
+        let stats: Stats = data.parse().unwrap();
+        assert_eq!(stats.games[0].puzzle, "a\"bA");
+        assert_eq!(stats.games[0].difficulty, Difficulty::Medium);
+        assert_eq!(stats.games[0].elapsed, Duration::from_secs(61));
+    }
+
+    #[test]
+    fn reports_bad_files() {
+        let error = |data: &str| data.parse::<Stats>().unwrap_err();
+        assert_eq!(error("{\"version\": 1,}"), "invalid JSON at byte 15");
+        assert_eq!(
+            error("{\"version\": 2, \"games\": []}"),
+            "version 2 is not supported, the latest is 1"
+        );
+        assert_eq!(error("{\"games\": []}"), "missing version");
+        assert_eq!(
+            error("{\"version\": 1, \"games\": [{\"puzzle\": \"1\"}]}"),
+            "game 1: missing difficulty"
+        );
+        assert_eq!(Stats::load("static/missing.json"), Ok(Stats::default()));
+    }
+
+    #[test]
+    fn formats_durations() {
+        assert_eq!(format_duration(Duration::from_millis(65_900)), "1:05");
+        assert_eq!(
+            format_duration(Duration::from_secs(3 * 3600 + 7)),
+            "3:00:07"
+        );
+    }
+}
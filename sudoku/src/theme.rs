@@ -0,0 +1,332 @@
+//! Themes for the gameboard view.
+//!
+//! A theme file is a flat TOML table naming the theme, optionally a font
+//! file, and any of the view settings apart from the board's position and
+//! size:
+//!
+//! ```toml
+//! name = "dusk"
+//! font = "assets/FiraSans-Regular.ttf"
+//! background_color = "#2a2b3d"
+//! text_color = [0.9, 0.9, 1.0, 1.0]
+//! section_edge_radius = 2.5
+//! hint_font_size = 16
+//! ```
+//!
+//! Colors are written as `"#rrggbb"`, `"#rrggbbaa"` or an array of red,
+//! green, blue and alpha from 0 to 1. Settings left out keep the value of
+//! the light theme.
+
+use graphics::types::Color;
+use std::fs::read_to_string;
+
+use crate::gameboard_view::GameboardViewSettings;
+
+/// Themes shipped with the game, in the order the theme key cycles them.
+static BUNDLED: [&str; 3] = [
+    include_str!("../assets/themes/light.toml"),
+    include_str!("../assets/themes/dark.toml"),
+    include_str!("../assets/themes/high-contrast.toml"),
+];
+
+/// A look for the gameboard view.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Theme {
+    /// Name of the theme.
+    pub name: String,
+    /// Font file to draw text with, if not the default.
+    pub font: Option<String>,
+    /// View settings, with the default position and size.
+    pub settings: GameboardViewSettings,
+}
+
+impl Theme {
+    /// The light, dark and high-contrast themes shipped with the game.
+    pub fn bundled() -> Vec<Theme> {
+        BUNDLED
+            .iter()
+            .map(|data| Theme::parse(data).expect("bundled themes are valid"))
+            .collect()
+    }
+
+    /// Finds the bundled theme called `name`, or else reads the theme file
+    /// `name`.
+    pub fn find(name: &str) -> Result<Theme, String> {
+        match Theme::bundled()
+            .into_iter()
+            .find(|theme| theme.name == name)
+        {
+            Some(theme) => Ok(theme),
+            None => Theme::load(name),
+        }
+    }
+
+    /// Reads the theme file `filename`.
+    pub fn load(filename: &str) -> Result<Theme, String> {
+        let data = read_to_string(filename).map_err(|e| {
+            format!("failed to read theme file `{}`: {}", filename, e)
+        })?;
+        Theme::parse(&data)
+            .map_err(|e| format!("invalid theme file `{}`: {}", filename, e))
+    }
+
+    /// Parses a theme, one `key = value` setting per line, skipping blank
+    /// lines and `#` comments.
+    pub fn parse(data: &str) -> Result<Theme, String> {
+        let mut theme = Theme {
+            name: String::new(),
+            font: None,
+            settings: GameboardViewSettings::new(),
+        };
+        for (i, line) in data.lines().enumerate() {
+            theme
+                .parse_line(line)
+                .map_err(|e| format!("line {}: {}", i + 1, e))?;
+        }
+        if theme.name.is_empty() {
+            return Err("missing name".into());
+        }
+        Ok(theme)
+    }
+
+    fn parse_line(&mut self, line: &str) -> Result<(), String> {
+        let line = strip_comment(line).trim();
+        if line.is_empty() {
+            return Ok(());
+        }
+        let (key, value) =
+            line.split_once('=').ok_or("expected `key = value`")?;
+        let (key, value) = (key.trim(), value.trim());
+        let settings = &mut self.settings;
+        if key == "name" {
+            self.name = parse_string(value)?;
+        } else if key == "font" {
+            self.font = Some(parse_string(value)?);
+        } else if let Some(color) = color_setting(settings, key) {
+            *color = parse_color(value)?;
+        } else if let Some(length) = length_setting(settings, key) {
+            *length = value
+                .parse()
+                .map_err(|_| format!("`{}` is not a number", value))?;
+        } else if let Some(font_size) = font_size_setting(settings, key) {
+            *font_size = value
+                .parse()
+                .map_err(|_| format!("`{}` is not a font size", value))?;
+        } else {
+            return Err(format!("unknown setting `{}`", key));
+        }
+        Ok(())
+    }
+
+    /// Gives `settings` the look of the theme, keeping the board where it
+    /// is.
+    pub fn apply(&self, settings: &mut GameboardViewSettings) {
+        *settings = GameboardViewSettings {
+            position: settings.position,
+            size: settings.size,
+            pixel_ratio: settings.pixel_ratio,
+            ..self.settings.clone()
+        };
+    }
+}
+
+fn color_setting<'a>(
+    settings: &'a mut GameboardViewSettings,
+    key: &str,
+) -> Option<&'a mut Color> {
+    let s = settings;
+    Some(match key {
+        "window_background_color" => &mut s.window_background_color,
+        "background_color" => &mut s.background_color,
+        "border_color" => &mut s.border_color,
+        "board_edge_color" => &mut s.board_edge_color,
+        "section_edge_color" => &mut s.section_edge_color,
+        "cell_edge_color" => &mut s.cell_edge_color,
+        "selected_cell_background_color" => {
+            &mut s.selected_cell_background_color
+        }
+        "notes_selected_cell_background_color" => {
+            &mut s.notes_selected_cell_background_color
+        }
+        "loaded_cell_background_color" => &mut s.loaded_cell_background_color,
+        "invalid_cell_background_color" => &mut s.invalid_cell_background_color,
+        "invalid_selected_cell_background_color" => {
+            &mut s.invalid_selected_cell_background_color
+        }
+        "peer_cell_highlight_color" => &mut s.peer_cell_highlight_color,
+        "same_value_cell_highlight_color" => {
+            &mut s.same_value_cell_highlight_color
+        }
+        "completed_background_color" => &mut s.completed_background_color,
+        "hint_cell_background_color" => &mut s.hint_cell_background_color,
+        "text_color" => &mut s.text_color,
+        "notes_text_color" => &mut s.notes_text_color,
+        "rule_line_color" => &mut s.rule_line_color,
+        "thermometer_color" => &mut s.thermometer_color,
+        "cage_edge_color" => &mut s.cage_edge_color,
+        "rule_text_color" => &mut s.rule_text_color,
+        "hint_text_color" => &mut s.hint_text_color,
+        "info_text_color" => &mut s.info_text_color,
+        "number_pad_background_color" => &mut s.number_pad_background_color,
+        "number_pad_edge_color" => &mut s.number_pad_edge_color,
+        "number_pad_text_color" => &mut s.number_pad_text_color,
+        _ => return None,
+    })
+}
+
+fn length_setting<'a>(
+    settings: &'a mut GameboardViewSettings,
+    key: &str,
+) -> Option<&'a mut f64> {
+    let s = settings;
+    Some(match key {
+        "margin" => &mut s.margin,
+        "board_edge_radius" => &mut s.board_edge_radius,
+        "section_edge_radius" => &mut s.section_edge_radius,
+        "cell_edge_radius" => &mut s.cell_edge_radius,
+        "rule_line_radius" => &mut s.rule_line_radius,
+        "cage_edge_radius" => &mut s.cage_edge_radius,
+        _ => return None,
+    })
+}
+
+fn font_size_setting<'a>(
+    settings: &'a mut GameboardViewSettings,
+    key: &str,
+) -> Option<&'a mut u32> {
+    let s = settings;
+    Some(match key {
+        "notes_font_size" => &mut s.notes_font_size,
+        "cage_font_size" => &mut s.cage_font_size,
+        "hint_font_size" => &mut s.hint_font_size,
+        "info_font_size" => &mut s.info_font_size,
+        _ => return None,
+    })
+}
+
+/// Cuts a `#` comment off `line`, leaving any `#` inside a string.
+fn strip_comment(line: &str) -> &str {
+    let mut quoted = false;
+    for (k, c) in line.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '#' if !quoted => return &line[..k],
+            _ => {}
+        }
+    }
+    line
+}
+
+fn parse_string(value: &str) -> Result<String, String> {
+    value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .filter(|value| !value.contains('"'))
+        .map(String::from)
+        .ok_or_else(|| format!("expected a quoted string, found `{}`", value))
+}
+
+fn parse_color(value: &str) -> Result<Color, String> {
+    let invalid = || format!("`{}` is not a color", value);
+    if let Some(list) = value.strip_prefix('[') {
+        let list = list.strip_suffix(']').ok_or_else(invalid)?;
+        let channels = list
+            .split(',')
+            .map(|channel| channel.trim().parse::<f32>())
+            .collect::<Result<Vec<f32>, _>>()
+            .map_err(|_| invalid())?;
+        return match channels[..] {
+            [r, g, b, a]
+                if channels.iter().all(|c| (0.0..=1.0).contains(c)) =>
+            {
+                Ok([r, g, b, a])
+            }
+            _ => Err(invalid()),
+        };
+    }
+    let hex = parse_string(value)
+        .ok()
+        .and_then(|s| s.strip_prefix('#').map(String::from))
+        .filter(|hex| hex.len() == 6 || hex.len() == 8)
+        .ok_or_else(invalid)?;
+    let mut color = [1.0; 4];
+    for (k, channel) in color.iter_mut().enumerate().take(hex.len() / 2) {
+        let byte = hex
+            .get(2 * k..2 * k + 2)
+            .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+            .ok_or_else(invalid)?;
+        *channel = byte as f32 / 255.0;
+    }
+    Ok(color)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bundles_themes() {
+        let themes = Theme::bundled();
+        let names: Vec<&str> =
+            themes.iter().map(|theme| theme.name.as_str()).collect();
+        assert_eq!(names, ["light", "dark", "high-contrast"]);
+        assert_eq!(themes[0].settings, GameboardViewSettings::new());
+        assert_eq!(themes[1].settings.text_color[3], 1.0);
+        assert_eq!(themes[2].settings.hint_font_size, 16);
+        assert_eq!(Theme::find("dark"), Ok(themes[1].clone()));
+    }
+
+    #[test]
+    fn parses_settings() {
+        let data = "# comment\nname = \"mine\" # trailing\n\
+                    font = \"a#b.ttf\"\n\
+                    text_color = \"#ff000080\"\n\
+                    background_color = [0.5, 0.25, 0, 1]\n\
+                    cell_edge_radius = 0.75\n\
+                    cage_font_size = 9\n";
+        let theme = Theme::parse(data).unwrap();
+        assert_eq!(theme.name, "mine");
+        assert_eq!(theme.font.as_deref(), Some("a#b.ttf"));
+        let settings = &theme.settings;
+        assert_eq!(settings.text_color, [1.0, 0.0, 0.0, 128.0 / 255.0]);
+        assert_eq!(settings.background_color, [0.5, 0.25, 0.0, 1.0]);
+        assert_eq!(settings.cell_edge_radius, 0.75);
+        assert_eq!(settings.cage_font_size, 9);
+        assert_eq!(settings.hint_font_size, 14);
+
+        let mut settings = GameboardViewSettings::new();
+        settings.size = 123.0;
+        theme.apply(&mut settings);
+        assert_eq!(settings.size, 123.0);
+        assert_eq!(settings.cage_font_size, 9);
+    }
+
+    #[test]
+    fn reports_bad_settings() {
+        let error = |data: &str| Theme::parse(data).unwrap_err();
+        assert_eq!(
+            error("text_color = \"#fff\""),
+            "line 1: `\"#fff\"` is not a color"
+        );
+        assert_eq!(
+            error("name = \"x\"\ncolor = [1, 1, 1, 1]"),
+            "line 2: unknown setting `color`"
+        );
+        assert_eq!(
+            error("name = x"),
+            "line 1: expected a quoted string, found `x`"
+        );
+        assert_eq!(
+            error("hint_font_size = 1.5"),
+            "line 1: `1.5` is not a font size"
+        );
+        assert_eq!(error("size = 10"), "line 1: unknown setting `size`");
+        assert_eq!(
+            error("text_color = [2, 0, 0, 1]"),
+            "line 1: `[2, 0, 0, 1]` is not a color"
+        );
+        assert_eq!(error("margin"), "line 1: expected `key = value`");
+        assert_eq!(error(""), "missing name");
+        assert!(Theme::find("missing.toml").is_err());
+    }
+}
@@ -0,0 +1,27 @@
+#![deny(missing_docs)]
+
+//! Helpers for testing the tutorials without a window.
+//!
+//! `RecordingGraphics` records what a render function draws,
+//! `RasterGraphics` draws it into pixels to compare with PNG files, and
+//! `InputScript` plays back input events to an event handler. Tutorials
+//! using piston2d-graphics 0.44 depend on this crate with
+//! `default-features = false, features = ["graphics_0_44"]`.
+
+#[cfg(all(feature = "graphics_0_42", not(feature = "graphics_0_44")))]
+extern crate graphics_0_42 as graphics;
+#[cfg(feature = "graphics_0_44")]
+extern crate graphics_0_44 as graphics;
+
+#[cfg(not(any(feature = "graphics_0_42", feature = "graphics_0_44")))]
+compile_error!("enable the `graphics_0_42` or `graphics_0_44` feature");
+
+pub use crate::raster::{RasterGlyphs, RasterGraphics, RasterTexture};
+pub use crate::recording::{
+    Draw, RecordedTexture, RecordingGlyphs, RecordingGraphics,
+};
+pub use crate::script::InputScript;
+
+pub mod raster;
+pub mod recording;
+pub mod script;
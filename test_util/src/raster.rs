@@ -0,0 +1,523 @@
+//! Software rendering into an RGBA buffer.
+//!
+//! `RasterGraphics` implements `graphics::Graphics` on the CPU, so views can
+//! be drawn and compared against PNG files without an OpenGL context.
+//! `RasterGlyphs` is the matching glyph cache, rasterizing a TrueType font.
+//!
+//! Triangles are filled where they cover the center of a pixel, without
+//! anti-aliasing, and textures are sampled at the nearest texel. The draw
+//! state is ignored.
+
+use graphics::character::{Character, CharacterCache};
+use graphics::types::{Color, FontSize};
+use graphics::{Context, DrawState, Graphics, ImageSize};
+use rusttype::{point, Font, Scale};
+use std::collections::HashMap;
+use std::fs::{read, File};
+use std::io::{self, BufWriter};
+
+/// Subpixel steps per pixel that vertices are snapped to.
+const SUBPIXELS: i64 = 16;
+
+/// An RGBA image drawn on the CPU.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RasterGraphics {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+impl RasterGraphics {
+    /// Creates a transparent image of `width` by `height` pixels.
+    pub fn new(width: u32, height: u32) -> RasterGraphics {
+        RasterGraphics {
+            width,
+            height,
+            pixels: vec![0; width as usize * height as usize * 4],
+        }
+    }
+
+    /// Width in pixels.
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Height in pixels.
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Red, green, blue and alpha of every pixel, row by row from the top.
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    /// Red, green, blue and alpha of the pixel at column `x` and row `y`.
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        let k = (y as usize * self.width as usize + x as usize) * 4;
+        [
+            self.pixels[k],
+            self.pixels[k + 1],
+            self.pixels[k + 2],
+            self.pixels[k + 3],
+        ]
+    }
+
+    /// A context drawing in pixels with the origin at the top left.
+    pub fn context(&self) -> Context {
+        Context::new_abs(self.width as f64, self.height as f64)
+    }
+
+    /// Reads an RGBA PNG file.
+    pub fn load_png(filename: &str) -> io::Result<RasterGraphics> {
+        let invalid = |e: png::DecodingError| {
+            io::Error::new(io::ErrorKind::InvalidData, e.to_string())
+        };
+        let decoder = png::Decoder::new(File::open(filename)?);
+        let mut reader = decoder.read_info().map_err(invalid)?;
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut pixels).map_err(invalid)?;
+        if info.color_type != png::ColorType::Rgba
+            || info.bit_depth != png::BitDepth::Eight
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("`{}` is not an 8-bit RGBA image", filename),
+            ));
+        }
+        pixels.truncate(info.buffer_size());
+        Ok(RasterGraphics {
+            width: info.width,
+            height: info.height,
+            pixels,
+        })
+    }
+
+    /// Writes the image as an RGBA PNG file.
+    pub fn save_png(&self, filename: &str) -> io::Result<()> {
+        let invalid = |e: png::EncodingError| io::Error::other(e.to_string());
+        let file = BufWriter::new(File::create(filename)?);
+        let mut encoder = png::Encoder::new(file, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().map_err(invalid)?;
+        writer.write_image_data(&self.pixels).map_err(invalid)
+    }
+
+    /// Number of pixels where a channel differs from `other` by more than
+    /// `tolerance`, or `None` if the images differ in size.
+    pub fn diff(&self, other: &RasterGraphics, tolerance: u8) -> Option<usize> {
+        if (self.width, self.height) != (other.width, other.height) {
+            return None;
+        }
+        let differs = self
+            .pixels
+            .chunks(4)
+            .zip(other.pixels.chunks(4))
+            .filter(|(a, b)| {
+                a.iter()
+                    .zip(b.iter())
+                    .any(|(a, b)| a.abs_diff(*b) > tolerance)
+            })
+            .count();
+        Some(differs)
+    }
+
+    /// Blends `color` over the pixel at column `x` and row `y`.
+    fn blend(&mut self, x: i64, y: i64, color: [f32; 4]) {
+        let k = (y as usize * self.width as usize + x as usize) * 4;
+        let pixel = &mut self.pixels[k..k + 4];
+        let alpha = color[3].clamp(0.0, 1.0);
+        let below = pixel[3] as f32 / 255.0 * (1.0 - alpha);
+        let out_alpha = alpha + below;
+        for c in 0..3 {
+            let dst = pixel[c] as f32 / 255.0;
+            let value = if out_alpha > 0.0 {
+                (color[c].clamp(0.0, 1.0) * alpha + dst * below) / out_alpha
+            } else {
+                0.0
+            };
+            pixel[c] = (value * 255.0).round() as u8;
+        }
+        pixel[3] = (out_alpha * 255.0).round() as u8;
+    }
+
+    /// Fills the triangle with corners `tri` in normalized device
+    /// coordinates, coloring each pixel by `shade` of the weights of the
+    /// corners at its center.
+    fn fill<F>(&mut self, tri: &[[f32; 2]], mut shade: F)
+    where
+        F: FnMut([f32; 3]) -> [f32; 4],
+    {
+        let (w, h) = (self.width as f64, self.height as f64);
+        let mut v = [[0i64; 2]; 3];
+        for (v, &[x, y]) in v.iter_mut().zip(tri.iter()) {
+            if !x.is_finite() || !y.is_finite() {
+                return;
+            }
+            let x = (x as f64 + 1.0) / 2.0 * w;
+            let y = (1.0 - y as f64) / 2.0 * h;
+            *v = [
+                (x * SUBPIXELS as f64).round() as i64,
+                (y * SUBPIXELS as f64).round() as i64,
+            ];
+        }
+        // Put the corners in the order that makes the area positive, and
+        // remember which weight belongs to which corner.
+        let mut order = [0, 1, 2];
+        let mut area = edge(v[0], v[1], v[2]);
+        if area < 0 {
+            v.swap(1, 2);
+            order.swap(1, 2);
+            area = -area;
+        }
+        if area == 0 {
+            return;
+        }
+        // Pixels exactly on an edge belong to the triangle to its right or
+        // below it, so triangles sharing an edge never cover a pixel twice.
+        let bias = |a: [i64; 2], b: [i64; 2]| {
+            let (dx, dy) = (b[0] - a[0], b[1] - a[1]);
+            if dy < 0 || (dy == 0 && dx > 0) {
+                0
+            } else {
+                -1
+            }
+        };
+        let biases = [bias(v[1], v[2]), bias(v[2], v[0]), bias(v[0], v[1])];
+        let min = |k: usize| v.iter().map(|p| p[k]).min().unwrap();
+        let max = |k: usize| v.iter().map(|p| p[k]).max().unwrap();
+        let x0 = (min(0) / SUBPIXELS).max(0);
+        let y0 = (min(1) / SUBPIXELS).max(0);
+        let x1 = (max(0) / SUBPIXELS + 1).min(self.width as i64);
+        let y1 = (max(1) / SUBPIXELS + 1).min(self.height as i64);
+        for y in y0..y1 {
+            for x in x0..x1 {
+                let p = [
+                    x * SUBPIXELS + SUBPIXELS / 2,
+                    y * SUBPIXELS + SUBPIXELS / 2,
+                ];
+                let e = [
+                    edge(v[1], v[2], p),
+                    edge(v[2], v[0], p),
+                    edge(v[0], v[1], p),
+                ];
+                if e.iter().zip(biases.iter()).any(|(e, b)| e + b < 0) {
+                    continue;
+                }
+                let mut weights = [0.0; 3];
+                for (k, &corner) in order.iter().enumerate() {
+                    weights[corner] = e[k] as f32 / area as f32;
+                }
+                let color = shade(weights);
+                self.blend(x, y, color);
+            }
+        }
+    }
+}
+
+/// Twice the signed area of the triangle `a`, `b`, `p`, positive when `p`
+/// lies clockwise from `a` to `b` on screen.
+fn edge(a: [i64; 2], b: [i64; 2], p: [i64; 2]) -> i64 {
+    (b[0] - a[0]) * (p[1] - a[1]) - (b[1] - a[1]) * (p[0] - a[0])
+}
+
+/// Interpolates the values at the corners of a triangle by `weights`.
+fn mix<const N: usize>(values: &[[f32; N]], weights: [f32; 3]) -> [f32; N] {
+    let mut mixed = [0.0; N];
+    for (value, weight) in values.iter().zip(weights.iter()) {
+        for (mixed, value) in mixed.iter_mut().zip(value.iter()) {
+            *mixed += value * weight;
+        }
+    }
+    mixed
+}
+
+/// Multiplies two colors channel by channel.
+fn tint(a: [f32; 4], b: [f32; 4]) -> [f32; 4] {
+    [a[0] * b[0], a[1] * b[1], a[2] * b[2], a[3] * b[3]]
+}
+
+impl Graphics for RasterGraphics {
+    type Texture = RasterTexture;
+
+    fn clear_color(&mut self, color: Color) {
+        let pixel = color.map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8);
+        for chunk in self.pixels.chunks_mut(4) {
+            chunk.copy_from_slice(&pixel);
+        }
+    }
+
+    fn clear_stencil(&mut self, _value: u8) {}
+
+    fn tri_list<F>(
+        &mut self,
+        _draw_state: &DrawState,
+        color: &[f32; 4],
+        mut f: F,
+    ) where
+        F: FnMut(&mut dyn FnMut(&[[f32; 2]])),
+    {
+        f(&mut |vertices| {
+            for tri in vertices.chunks_exact(3) {
+                self.fill(tri, |_| *color);
+            }
+        })
+    }
+
+    fn tri_list_c<F>(&mut self, _draw_state: &DrawState, mut f: F)
+    where
+        F: FnMut(&mut dyn FnMut(&[[f32; 2]], &[[f32; 4]])),
+    {
+        f(&mut |vertices, colors| {
+            let tris = vertices.chunks_exact(3).zip(colors.chunks_exact(3));
+            for (tri, colors) in tris {
+                self.fill(tri, |weights| mix(colors, weights));
+            }
+        })
+    }
+
+    fn tri_list_uv<F>(
+        &mut self,
+        _draw_state: &DrawState,
+        color: &[f32; 4],
+        texture: &RasterTexture,
+        mut f: F,
+    ) where
+        F: FnMut(&mut dyn FnMut(&[[f32; 2]], &[[f32; 2]])),
+    {
+        f(&mut |vertices, uvs| {
+            let tris = vertices.chunks_exact(3).zip(uvs.chunks_exact(3));
+            for (tri, uvs) in tris {
+                self.fill(tri, |weights| {
+                    tint(texture.sample(mix(uvs, weights)), *color)
+                });
+            }
+        })
+    }
+
+    fn tri_list_uv_c<F>(
+        &mut self,
+        _draw_state: &DrawState,
+        texture: &RasterTexture,
+        mut f: F,
+    ) where
+        F: FnMut(&mut dyn FnMut(&[[f32; 2]], &[[f32; 2]], &[[f32; 4]])),
+    {
+        f(&mut |vertices, uvs, colors| {
+            let tris = vertices
+                .chunks_exact(3)
+                .zip(uvs.chunks_exact(3))
+                .zip(colors.chunks_exact(3));
+            for ((tri, uvs), colors) in tris {
+                self.fill(tri, |weights| {
+                    let texel = texture.sample(mix(uvs, weights));
+                    tint(texel, mix(colors, weights))
+                });
+            }
+        })
+    }
+}
+
+/// An RGBA texture for `RasterGraphics`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RasterTexture {
+    width: u32,
+    height: u32,
+    pixels: Vec<[f32; 4]>,
+}
+
+impl RasterTexture {
+    /// Creates a texture from the colors of `width` by `height` pixels, row
+    /// by row from the top.
+    pub fn new(
+        width: u32,
+        height: u32,
+        pixels: Vec<[f32; 4]>,
+    ) -> RasterTexture {
+        assert_eq!(pixels.len(), width as usize * height as usize);
+        RasterTexture {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    /// The texel nearest to `uv`, which runs from 0 to 1 across the texture.
+    fn sample(&self, uv: [f32; 2]) -> [f32; 4] {
+        if self.pixels.is_empty() {
+            return [0.0; 4];
+        }
+        let texel = |t: f32, size: u32| {
+            ((t * size as f32) as i64).clamp(0, size as i64 - 1) as usize
+        };
+        let (x, y) = (texel(uv[0], self.width), texel(uv[1], self.height));
+        self.pixels[y * self.width as usize + x]
+    }
+}
+
+impl ImageSize for RasterTexture {
+    fn get_size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+}
+
+/// A rasterized character and where to draw it.
+struct Glyph {
+    offset: [f64; 2],
+    advance: f64,
+    texture: RasterTexture,
+}
+
+/// Glyph cache for `RasterGraphics`, sizing fonts like the OpenGL glyph
+/// cache.
+pub struct RasterGlyphs {
+    font: Font<'static>,
+    glyphs: HashMap<(FontSize, char), Glyph>,
+}
+
+impl RasterGlyphs {
+    /// Reads the TrueType font `filename`.
+    pub fn load(filename: &str) -> Result<RasterGlyphs, String> {
+        let data = read(filename).map_err(|e| {
+            format!("failed to read font `{}`: {}", filename, e)
+        })?;
+        let font = Font::try_from_vec(data)
+            .ok_or_else(|| format!("invalid font `{}`", filename))?;
+        Ok(RasterGlyphs {
+            font,
+            glyphs: HashMap::new(),
+        })
+    }
+
+    fn rasterize(&self, font_size: FontSize, ch: char) -> Glyph {
+        // Font sizes are in points, rendered at 96 pixels per inch.
+        let pixels = (font_size as f32 * 1.333).round();
+        let glyph = self.font.glyph(ch).scaled(Scale::uniform(pixels));
+        let advance = glyph.h_metrics().advance_width as f64;
+        let glyph = glyph.positioned(point(0.0, 0.0));
+        let bounds = match glyph.pixel_bounding_box() {
+            Some(bounds) => bounds,
+            None => {
+                return Glyph {
+                    offset: [0.0; 2],
+                    advance,
+                    texture: RasterTexture::new(1, 1, vec![[0.0; 4]]),
+                }
+            }
+        };
+        let (width, height) = (bounds.width() as u32, bounds.height() as u32);
+        let mut pixels = vec![[1.0, 1.0, 1.0, 0.0]; (width * height) as usize];
+        glyph.draw(|x, y, coverage| {
+            pixels[(y * width + x) as usize][3] = coverage;
+        });
+        Glyph {
+            offset: [bounds.min.x as f64, -bounds.min.y as f64],
+            advance,
+            texture: RasterTexture::new(width, height, pixels),
+        }
+    }
+}
+
+impl CharacterCache for RasterGlyphs {
+    type Texture = RasterTexture;
+    type Error = String;
+
+    fn character(
+        &mut self,
+        font_size: FontSize,
+        ch: char,
+    ) -> Result<Character<'_, RasterTexture>, String> {
+        if !self.glyphs.contains_key(&(font_size, ch)) {
+            let glyph = self.rasterize(font_size, ch);
+            self.glyphs.insert((font_size, ch), glyph);
+        }
+        let glyph = &self.glyphs[&(font_size, ch)];
+        let (width, height) = glyph.texture.get_size();
+        Ok(Character {
+            offset: glyph.offset,
+            advance_size: [glyph.advance, 0.0],
+            atlas_offset: [0.0; 2],
+            atlas_size: [width as f64, height as f64],
+            texture: &glyph.texture,
+            is_invalid: false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Fills the pixel rectangle `[x, y, w, h]` of `g` with two triangles.
+    fn fill_rect(g: &mut RasterGraphics, rect: [f32; 4], color: Color) {
+        let (w, h) = (g.width() as f32, g.height() as f32);
+        let ndc = |x: f32, y: f32| [x / w * 2.0 - 1.0, 1.0 - y / h * 2.0];
+        let [x, y, rw, rh] = rect;
+        let corners = [
+            ndc(x, y),
+            ndc(x + rw, y),
+            ndc(x + rw, y + rh),
+            ndc(x, y + rh),
+        ];
+        let tris = [
+            corners[0], corners[1], corners[2], corners[0], corners[2],
+            corners[3],
+        ];
+        g.tri_list(&DrawState::default(), &color, |f| f(&tris));
+    }
+
+    #[test]
+    fn fills_triangles_once() {
+        let mut g = RasterGraphics::new(8, 6);
+        g.clear_color([1.0; 4]);
+        assert_eq!(g.pixel(0, 0), [255; 4]);
+        fill_rect(&mut g, [2.0, 1.0, 4.0, 4.0], [0.0, 0.0, 0.0, 0.5]);
+        // The shared diagonal is not blended twice.
+        for y in 1..5 {
+            for x in 2..6 {
+                assert_eq!(g.pixel(x, y), [128, 128, 128, 255], "{} {}", x, y);
+            }
+        }
+        assert_eq!(g.pixel(1, 1), [255; 4]);
+        assert_eq!(g.pixel(6, 1), [255; 4]);
+        assert_eq!(g.pixel(2, 5), [255; 4]);
+        // Neighbouring rectangles do not overlap either.
+        fill_rect(&mut g, [0.0, 0.0, 2.0, 6.0], [1.0, 0.0, 0.0, 1.0]);
+        assert_eq!(g.pixel(1, 3), [255, 0, 0, 255]);
+        assert_eq!(g.pixel(2, 3), [128, 128, 128, 255]);
+    }
+
+    #[test]
+    fn samples_textures() {
+        let texture = RasterTexture::new(
+            2,
+            1,
+            vec![[1.0, 0.0, 0.0, 1.0], [0.0, 0.0, 1.0, 1.0]],
+        );
+        let mut g = RasterGraphics::new(4, 4);
+        let tris = [[-1.0, 1.0], [1.0, 1.0], [1.0, -1.0]];
+        let uvs = [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0]];
+        let color = [1.0, 1.0, 1.0, 0.5];
+        g.tri_list_uv(&DrawState::default(), &color, &texture, |f| {
+            f(&tris, &uvs)
+        });
+        assert_eq!(g.pixel(1, 0), [255, 0, 0, 128]);
+        assert_eq!(g.pixel(3, 0), [0, 0, 255, 128]);
+        assert_eq!(g.pixel(0, 3), [0; 4]);
+    }
+
+    #[test]
+    fn round_trips_png() {
+        let mut g = RasterGraphics::new(3, 2);
+        g.clear_color([0.2, 0.4, 0.6, 1.0]);
+        let file = std::env::temp_dir().join("raster-round-trip.png");
+        let file = file.to_str().unwrap();
+        g.save_png(file).unwrap();
+        let loaded = RasterGraphics::load_png(file).unwrap();
+        assert_eq!(loaded, g);
+        assert_eq!(g.diff(&loaded, 0), Some(0));
+        assert_eq!(g.diff(&RasterGraphics::new(2, 3), 0), None);
+        std::fs::remove_file(file).unwrap();
+    }
+}
@@ -0,0 +1,441 @@
+//! A graphics backend recording what is drawn.
+//!
+//! `RecordingGraphics` implements `graphics::Graphics` by logging every
+//! rectangle, line, ellipse, polygon, image and glyph instead of drawing
+//! it, so tests can check what a view drew and with which colors.
+//! Positions are in window pixels, after the transform of the draw call.
+//! `RecordingGlyphs` is the matching glyph cache, giving every character a
+//! box of fixed proportions so glyphs can be told apart from images.
+
+use graphics::character::{Character, CharacterCache};
+use graphics::math::Matrix2d;
+use graphics::types::{self, Color, FontSize};
+use graphics::{
+    Context, DrawState, Ellipse, Graphics, Image, ImageSize, Line, Polygon,
+    Rectangle,
+};
+use std::collections::HashMap;
+
+/// Something drawn, in window pixels.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Draw {
+    /// The window was cleared.
+    Clear(Color),
+    /// A rectangle, with its bounds as `[x, y, width, height]`.
+    Rectangle {
+        /// Bounds of the rectangle.
+        rect: [f64; 4],
+        /// Fill color, transparent for a border only.
+        color: Color,
+        /// Color and radius of the border.
+        border: Option<(Color, f64)>,
+    },
+    /// An ellipse inside `rect`.
+    Ellipse {
+        /// Bounds of the ellipse.
+        rect: [f64; 4],
+        /// Fill color.
+        color: Color,
+    },
+    /// A line between two points, as `[x1, y1, x2, y2]`.
+    Line {
+        /// Ends of the line.
+        line: [f64; 4],
+        /// Color of the line.
+        color: Color,
+        /// Radius of the line before the transform.
+        radius: f64,
+    },
+    /// A filled polygon.
+    Polygon {
+        /// Corners of the polygon.
+        points: Vec<[f64; 2]>,
+        /// Fill color.
+        color: Color,
+    },
+    /// An image other than a glyph.
+    Image {
+        /// Bounds of the image.
+        rect: [f64; 4],
+        /// Color multiplied with the image.
+        color: Option<Color>,
+    },
+    /// A character drawn by `RecordingGlyphs`.
+    Glyph {
+        /// The character.
+        ch: char,
+        /// Font size the character was asked for at.
+        font_size: FontSize,
+        /// Bounds of the glyph.
+        rect: [f64; 4],
+        /// Color of the glyph.
+        color: Color,
+    },
+    /// Triangles drawn directly, without a shape.
+    Triangles {
+        /// Number of triangles.
+        count: usize,
+        /// Color of the triangles, if they have one color.
+        color: Option<Color>,
+    },
+}
+
+/// Records the draw calls made on a window of a given size.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RecordingGraphics {
+    /// Size of the window in pixels.
+    pub window_size: [f64; 2],
+    /// Everything drawn so far, in order.
+    pub draws: Vec<Draw>,
+}
+
+impl RecordingGraphics {
+    /// Creates a recorder for a window of `width` by `height` pixels.
+    pub fn new(width: f64, height: f64) -> RecordingGraphics {
+        RecordingGraphics {
+            window_size: [width, height],
+            draws: vec![],
+        }
+    }
+
+    /// A context drawing in pixels with the origin at the top left.
+    pub fn context(&self) -> Context {
+        Context::new_abs(self.window_size[0], self.window_size[1])
+    }
+
+    /// The characters drawn, in order.
+    pub fn text(&self) -> String {
+        self.draws
+            .iter()
+            .filter_map(|draw| match *draw {
+                Draw::Glyph { ch, .. } => Some(ch),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Colors of the rectangles filling exactly `rect`, in the order they
+    /// were drawn.
+    pub fn fills(&self, rect: [f64; 4]) -> Vec<Color> {
+        let close = |a: [f64; 4]| {
+            a.iter().zip(rect.iter()).all(|(a, b)| (a - b).abs() < 1e-6)
+        };
+        self.draws
+            .iter()
+            .filter_map(|draw| match *draw {
+                Draw::Rectangle { rect, color, .. }
+                    if close(rect) && color[3] > 0.0 =>
+                {
+                    Some(color)
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Where `pos` ends up in the window after `transform`, rounded to a
+    /// millionth of a pixel to hide the rounding of the transform.
+    fn to_window(&self, transform: Matrix2d, pos: [f64; 2]) -> [f64; 2] {
+        let [x, y] = pos;
+        let ndc = [
+            transform[0][0] * x + transform[0][1] * y + transform[0][2],
+            transform[1][0] * x + transform[1][1] * y + transform[1][2],
+        ];
+        let round = |v: f64| (v * 1e6).round() / 1e6;
+        [
+            round((ndc[0] + 1.0) / 2.0 * self.window_size[0]),
+            round((1.0 - ndc[1]) / 2.0 * self.window_size[1]),
+        ]
+    }
+
+    /// Bounds in the window of `rect` after `transform`.
+    fn bounds(&self, transform: Matrix2d, rect: [f64; 4]) -> [f64; 4] {
+        let [x, y, w, h] = rect;
+        let corners = [[x, y], [x + w, y], [x, y + h], [x + w, y + h]]
+            .map(|corner| self.to_window(transform, corner));
+        let min =
+            |k: usize| corners.iter().map(|c| c[k]).fold(f64::MAX, f64::min);
+        let max =
+            |k: usize| corners.iter().map(|c| c[k]).fold(f64::MIN, f64::max);
+        [min(0), min(1), max(0) - min(0), max(1) - min(1)]
+    }
+}
+
+impl Graphics for RecordingGraphics {
+    type Texture = RecordedTexture;
+
+    fn clear_color(&mut self, color: Color) {
+        self.draws.push(Draw::Clear(color));
+    }
+
+    fn clear_stencil(&mut self, _value: u8) {}
+
+    fn tri_list<F>(
+        &mut self,
+        _draw_state: &DrawState,
+        color: &[f32; 4],
+        mut f: F,
+    ) where
+        F: FnMut(&mut dyn FnMut(&[[f32; 2]])),
+    {
+        let mut count = 0;
+        f(&mut |vertices| count += vertices.len() / 3);
+        self.draws.push(Draw::Triangles {
+            count,
+            color: Some(*color),
+        });
+    }
+
+    fn tri_list_c<F>(&mut self, _draw_state: &DrawState, mut f: F)
+    where
+        F: FnMut(&mut dyn FnMut(&[[f32; 2]], &[[f32; 4]])),
+    {
+        let mut count = 0;
+        f(&mut |vertices, _| count += vertices.len() / 3);
+        self.draws.push(Draw::Triangles { count, color: None });
+    }
+
+    fn tri_list_uv<F>(
+        &mut self,
+        _draw_state: &DrawState,
+        color: &[f32; 4],
+        _texture: &RecordedTexture,
+        mut f: F,
+    ) where
+        F: FnMut(&mut dyn FnMut(&[[f32; 2]], &[[f32; 2]])),
+    {
+        let mut count = 0;
+        f(&mut |vertices, _| count += vertices.len() / 3);
+        self.draws.push(Draw::Triangles {
+            count,
+            color: Some(*color),
+        });
+    }
+
+    fn tri_list_uv_c<F>(
+        &mut self,
+        _draw_state: &DrawState,
+        _texture: &RecordedTexture,
+        mut f: F,
+    ) where
+        F: FnMut(&mut dyn FnMut(&[[f32; 2]], &[[f32; 2]], &[[f32; 4]])),
+    {
+        let mut count = 0;
+        f(&mut |vertices, _, _| count += vertices.len() / 3);
+        self.draws.push(Draw::Triangles { count, color: None });
+    }
+
+    fn rectangle<R: Into<types::Rectangle>>(
+        &mut self,
+        r: &Rectangle,
+        rectangle: R,
+        _draw_state: &DrawState,
+        transform: Matrix2d,
+    ) {
+        let rect = self.bounds(transform, rectangle.into());
+        self.draws.push(Draw::Rectangle {
+            rect,
+            color: r.color,
+            border: r.border.map(|border| (border.color, border.radius)),
+        });
+    }
+
+    fn polygon(
+        &mut self,
+        p: &Polygon,
+        polygon: types::Polygon,
+        _draw_state: &DrawState,
+        transform: Matrix2d,
+    ) {
+        let points = polygon
+            .iter()
+            .map(|&point| self.to_window(transform, point))
+            .collect();
+        self.draws.push(Draw::Polygon {
+            points,
+            color: p.color,
+        });
+    }
+
+    fn image(
+        &mut self,
+        image: &Image,
+        texture: &RecordedTexture,
+        _draw_state: &DrawState,
+        transform: Matrix2d,
+    ) {
+        let (width, height) = texture.get_size();
+        let size = image
+            .source_rectangle
+            .map_or([width as f64, height as f64], |src| [src[2], src[3]]);
+        let rect = image.rectangle.unwrap_or([0.0, 0.0, size[0], size[1]]);
+        let rect = self.bounds(transform, rect);
+        self.draws.push(match texture.glyph {
+            Some((ch, font_size)) => Draw::Glyph {
+                ch,
+                font_size,
+                rect,
+                color: image.color.unwrap_or([1.0; 4]),
+            },
+            None => Draw::Image {
+                rect,
+                color: image.color,
+            },
+        });
+    }
+
+    fn ellipse<R: Into<types::Rectangle>>(
+        &mut self,
+        e: &Ellipse,
+        rectangle: R,
+        _draw_state: &DrawState,
+        transform: Matrix2d,
+    ) {
+        let rect = self.bounds(transform, rectangle.into());
+        self.draws.push(Draw::Ellipse {
+            rect,
+            color: e.color,
+        });
+    }
+
+    fn line<L: Into<types::Line>>(
+        &mut self,
+        l: &Line,
+        line: L,
+        _draw_state: &DrawState,
+        transform: Matrix2d,
+    ) {
+        let [x1, y1, x2, y2] = line.into();
+        let [x1, y1] = self.to_window(transform, [x1, y1]);
+        let [x2, y2] = self.to_window(transform, [x2, y2]);
+        self.draws.push(Draw::Line {
+            line: [x1, y1, x2, y2],
+            color: l.color,
+            radius: l.radius,
+        });
+    }
+}
+
+/// A texture for `RecordingGraphics`, remembering the character it shows
+/// when it comes from `RecordingGlyphs`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecordedTexture {
+    /// Width in pixels.
+    pub width: u32,
+    /// Height in pixels.
+    pub height: u32,
+    /// Character and font size of a glyph.
+    pub glyph: Option<(char, FontSize)>,
+}
+
+impl ImageSize for RecordedTexture {
+    fn get_size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+}
+
+/// Glyph cache for `RecordingGraphics`.
+///
+/// Every glyph is as tall as the font size and six tenths as wide, with
+/// the baseline four fifths of the way down.
+#[derive(Clone, Debug, Default)]
+pub struct RecordingGlyphs {
+    glyphs: HashMap<(FontSize, char), RecordedTexture>,
+}
+
+impl RecordingGlyphs {
+    /// Creates an empty glyph cache.
+    pub fn new() -> RecordingGlyphs {
+        RecordingGlyphs::default()
+    }
+}
+
+impl CharacterCache for RecordingGlyphs {
+    type Texture = RecordedTexture;
+    type Error = String;
+
+    fn character(
+        &mut self,
+        font_size: FontSize,
+        ch: char,
+    ) -> Result<Character<'_, RecordedTexture>, String> {
+        let texture = self.glyphs.entry((font_size, ch)).or_insert_with(|| {
+            RecordedTexture {
+                width: (font_size as f64 * 0.6).round() as u32,
+                height: font_size,
+                glyph: Some((ch, font_size)),
+            }
+        });
+        let size = [texture.width as f64, texture.height as f64];
+        Ok(Character {
+            offset: [0.0, font_size as f64 * 0.8],
+            advance_size: [size[0], 0.0],
+            atlas_offset: [0.0; 2],
+            atlas_size: size,
+            texture,
+            is_invalid: false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use graphics::{Text, Transformed};
+
+    #[test]
+    fn records_shapes_in_window_pixels() {
+        let mut g = RecordingGraphics::new(200.0, 100.0);
+        let c = g.context();
+        graphics::clear([1.0; 4], &mut g);
+        let red = [1.0, 0.0, 0.0, 1.0];
+        Rectangle::new(red).draw(
+            [10.0, 20.0, 30.0, 40.0],
+            &c.draw_state,
+            c.transform.trans(5.0, 5.0),
+            &mut g,
+        );
+        Line::new(red, 2.0).draw(
+            [0.0, 0.0, 10.0, 0.0],
+            &c.draw_state,
+            c.transform.scale(2.0, 2.0),
+            &mut g,
+        );
+        assert_eq!(g.draws[0], Draw::Clear([1.0; 4]));
+        assert_eq!(g.fills([15.0, 25.0, 30.0, 40.0]), vec![red]);
+        match g.draws[2] {
+            Draw::Line { line, radius, .. } => {
+                assert_eq!(line, [0.0, 0.0, 20.0, 0.0]);
+                assert_eq!(radius, 2.0);
+            }
+            ref draw => panic!("expected a line, found {:?}", draw),
+        }
+    }
+
+    #[test]
+    fn records_glyphs() {
+        let mut g = RecordingGraphics::new(100.0, 100.0);
+        let mut glyphs = RecordingGlyphs::new();
+        let c = g.context();
+        let blue = [0.0, 0.0, 1.0, 1.0];
+        Text::new_color(blue, 10)
+            .draw(
+                "hi",
+                &mut glyphs,
+                &c.draw_state,
+                c.transform.trans(0.0, 20.0),
+                &mut g,
+            )
+            .unwrap();
+        assert_eq!(g.text(), "hi");
+        assert_eq!(
+            g.draws[1],
+            Draw::Glyph {
+                ch: 'i',
+                font_size: 10,
+                rect: [6.0, 12.0, 6.0, 10.0],
+                color: blue,
+            }
+        );
+    }
+}
@@ -0,0 +1,230 @@
+//! Scripted input for driving controllers without a window.
+//!
+//! A script lists input events separated by semicolons or new lines, and
+//! `#` starts a comment:
+//!
+//! ```text
+//! move 120 80; click left
+//! key D5; key ctrl+Z
+//! press LShift; key D3; release LShift
+//! scroll 0 -1
+//! unfocus; focus
+//! ```
+//!
+//! `move` sets the mouse cursor in window coordinates, `click` presses and
+//! releases a mouse button, `key` presses and releases a key named like
+//! `input::Key` in any case, holding control and shift around it if given
+//! with `ctrl+` and `shift+`, and `press` and `release` do one half of
+//! that. `scroll` moves the mouse wheel, and `focus` and `unfocus` give and
+//! take away the window focus.
+
+use input::{
+    Button, ButtonArgs, ButtonState, Event, Input, Key, Motion, MouseButton,
+};
+use std::fs::read_to_string;
+use std::str::FromStr;
+
+/// Input events to play back in order.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct InputScript {
+    events: Vec<Event>,
+}
+
+impl InputScript {
+    /// Loads a script from a file.
+    pub fn load(filename: &str) -> Result<InputScript, String> {
+        let data = read_to_string(filename)
+            .map_err(|e| format!("failed to read `{}`: {}", filename, e))?;
+        data.parse()
+            .map_err(|e| format!("in `{}`: {}", filename, e))
+    }
+
+    /// The events of the script.
+    pub fn events(&self) -> &[Event] {
+        &self.events
+    }
+
+    /// Hands every event in turn to `handle`, like the event loop would.
+    pub fn play<F: FnMut(&Event)>(&self, mut handle: F) {
+        for e in &self.events {
+            handle(e);
+        }
+    }
+
+    /// Adds the events of one command.
+    fn push(&mut self, command: &str) -> Result<(), String> {
+        let words: Vec<&str> = command.split_whitespace().collect();
+        match words[..] {
+            ["move", x, y] => {
+                let pos = [number(x)?, number(y)?];
+                self.push_input(Input::Move(Motion::MouseCursor(pos)));
+            }
+            ["scroll", x, y] => {
+                let ticks = [number(x)?, number(y)?];
+                self.push_input(Input::Move(Motion::MouseScroll(ticks)));
+            }
+            ["click", button] => {
+                let button = Button::Mouse(mouse_button(button)?);
+                self.push_button(ButtonState::Press, button);
+                self.push_button(ButtonState::Release, button);
+            }
+            ["key", chord] => {
+                let (modifiers, key) = chord_keys(chord)?;
+                let modifiers: Vec<Button> =
+                    modifiers.into_iter().map(Button::Keyboard).collect();
+                for &key in &modifiers {
+                    self.push_button(ButtonState::Press, key);
+                }
+                let key = Button::Keyboard(key);
+                self.push_button(ButtonState::Press, key);
+                self.push_button(ButtonState::Release, key);
+                for &key in modifiers.iter().rev() {
+                    self.push_button(ButtonState::Release, key);
+                }
+            }
+            ["press", name] => {
+                self.push_button(ButtonState::Press, button(name)?)
+            }
+            ["release", name] => {
+                self.push_button(ButtonState::Release, button(name)?)
+            }
+            ["focus"] => self.push_input(Input::Focus(true)),
+            ["unfocus"] => self.push_input(Input::Focus(false)),
+            _ => return Err(format!("unknown command `{}`", command)),
+        }
+        Ok(())
+    }
+
+    fn push_button(&mut self, state: ButtonState, button: Button) {
+        let args = ButtonArgs {
+            state,
+            button,
+            scancode: None,
+        };
+        self.push_input(Input::Button(args));
+    }
+
+    fn push_input(&mut self, input: Input) {
+        self.events.push(Event::Input(input, None));
+    }
+}
+
+impl FromStr for InputScript {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<InputScript, String> {
+        let mut script = InputScript::default();
+        for (n, line) in s.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("");
+            for command in line.split(';').map(str::trim) {
+                if !command.is_empty() {
+                    script
+                        .push(command)
+                        .map_err(|e| format!("line {}: {}", n + 1, e))?;
+                }
+            }
+        }
+        Ok(script)
+    }
+}
+
+fn number(s: &str) -> Result<f64, String> {
+    s.parse()
+        .map_err(|_| format!("expected a number, found `{}`", s))
+}
+
+fn mouse_button(s: &str) -> Result<MouseButton, String> {
+    match s {
+        "left" => Ok(MouseButton::Left),
+        "right" => Ok(MouseButton::Right),
+        "middle" => Ok(MouseButton::Middle),
+        _ => Err(format!("unknown mouse button `{}`", s)),
+    }
+}
+
+/// The modifier keys held for a key like `ctrl+Z`, and the key itself.
+fn chord_keys(s: &str) -> Result<(Vec<Key>, Key), String> {
+    let mut modifiers = vec![];
+    let mut name = s;
+    loop {
+        if let Some(rest) = name.strip_prefix("ctrl+") {
+            modifiers.push(Key::LCtrl);
+            name = rest;
+        } else if let Some(rest) = name.strip_prefix("shift+") {
+            modifiers.push(Key::LShift);
+            name = rest;
+        } else {
+            break;
+        }
+    }
+    // Key codes follow SDL: characters, then scancodes from 2^30.
+    let codes = (0..0x80).chain(0x4000_0000..0x4000_0120);
+    let key = codes
+        .map(Key::from)
+        .filter(|&key| key != Key::Unknown)
+        .find(|key| format!("{:?}", key).eq_ignore_ascii_case(name))
+        .ok_or_else(|| format!("unknown key `{}`", name))?;
+    Ok((modifiers, key))
+}
+
+/// A mouse button or a key without modifiers.
+fn button(s: &str) -> Result<Button, String> {
+    match mouse_button(s) {
+        Ok(button) => Ok(Button::Mouse(button)),
+        Err(_) => match chord_keys(s)? {
+            (modifiers, key) if modifiers.is_empty() => {
+                Ok(Button::Keyboard(key))
+            }
+            _ => Err(format!("`{}` presses more than one key", s)),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn holds_modifiers_around_keys() {
+        let script: InputScript = "key ctrl+shift+Z".parse().unwrap();
+        let keys: Vec<_> = script
+            .events()
+            .iter()
+            .map(|e| match e {
+                Event::Input(Input::Button(args), _) => {
+                    (args.state, args.button)
+                }
+                _ => panic!("unexpected event {:?}", e),
+            })
+            .collect();
+        let key = |state, key| (state, Button::Keyboard(key));
+        assert_eq!(
+            keys,
+            [
+                key(ButtonState::Press, Key::LCtrl),
+                key(ButtonState::Press, Key::LShift),
+                key(ButtonState::Press, Key::Z),
+                key(ButtonState::Release, Key::Z),
+                key(ButtonState::Release, Key::LShift),
+                key(ButtonState::Release, Key::LCtrl),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_bad_scripts() {
+        let parse = |s: &str| s.parse::<InputScript>().unwrap_err();
+        assert_eq!(parse("key D5\njump"), "line 2: unknown command `jump`");
+        assert_eq!(parse("move 1"), "line 1: unknown command `move 1`");
+        assert_eq!(parse("move 1 y"), "line 1: expected a number, found `y`");
+        assert_eq!(
+            parse("click thumb"),
+            "line 1: unknown mouse button `thumb`"
+        );
+        assert_eq!(parse("key Bogus"), "line 1: unknown key `Bogus`");
+        assert_eq!(
+            parse("press ctrl+A"),
+            "line 1: `ctrl+A` presses more than one key"
+        );
+    }
+}